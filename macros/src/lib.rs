@@ -0,0 +1,155 @@
+//! Procedural macros for hyperstone
+
+use proc_macro::TokenStream;
+use quote::{format_ident, quote};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input, Ident, ItemFn, LitStr, Token,
+};
+
+/// The arguments of `#[route(METHOD, "/path", name = "name")]`
+struct Args {
+    method: String,
+    path: LitStr,
+    name: Option<LitStr>,
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        let method = input.parse::<Ident>()?;
+        let method = match method.to_string().as_str() {
+            "ANY" => "*".to_owned(),
+            m @ ("GET" | "POST" | "PUT" | "DELETE" | "HEAD" | "OPTIONS" | "CONNECT" | "PATCH"
+            | "TRACE") => m.to_owned(),
+            _ => return Err(syn::Error::new(method.span(), "unknown method")),
+        };
+
+        input.parse::<Token![,]>()?;
+        let path = input.parse::<LitStr>()?;
+
+        let mut name = None;
+        if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let key = input.parse::<Ident>()?;
+            if key != "name" {
+                return Err(syn::Error::new(key.span(), "expected `name`"));
+            }
+            input.parse::<Token![=]>()?;
+            name.replace(input.parse::<LitStr>()?);
+            input.parse::<Option<Token![,]>>()?;
+        }
+
+        Ok(Self { method, path, name })
+    }
+}
+
+/// Registers an async handler to be collected by `Router::from_registered`.
+///
+/// ```ignore
+/// #[route(GET, "/users/:id", name = "user")]
+/// async fn user(req: Request<Body>) -> Result<Response<Body>> {
+///     // ...
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn route(args: TokenStream, item: TokenStream) -> TokenStream {
+    let Args { method, path, name } = parse_macro_input!(args as Args);
+    let item = parse_macro_input!(item as ItemFn);
+
+    if let Err(message) = check_pattern(&path.value()) {
+        return syn::Error::new(path.span(), message)
+            .to_compile_error()
+            .into();
+    }
+
+    if item.sig.asyncness.is_none() {
+        return syn::Error::new_spanned(item.sig.fn_token, "route handlers must be async")
+            .to_compile_error()
+            .into();
+    }
+
+    let ident = &item.sig.ident;
+    let handler = format_ident!("__hyperstone_route_{}", ident);
+    let name = match name {
+        Some(name) => quote!(::core::option::Option::Some(#name)),
+        None => quote!(::core::option::Option::None),
+    };
+
+    quote! {
+        #item
+
+        #[doc(hidden)]
+        #[allow(non_snake_case)]
+        fn #handler() -> ::hyperstone::BoxHandler {
+            ::hyperstone::BoxHandler::new(#ident)
+        }
+
+        ::hyperstone::inventory::submit! {
+            ::hyperstone::RegisteredRoute::new(#method, #path, #name, #handler)
+        }
+    }
+    .into()
+}
+
+/// Checks a route pattern at compile time and expands to it as a string literal.
+///
+/// Rejects unbalanced braces, empty or duplicate parameter names, unknown `{name:type}`
+/// types and catch-alls before the last segment.
+///
+/// ```ignore
+/// Router::new().get(path!("/users/{id:int}/posts/:post"), handler)
+/// ```
+#[proc_macro]
+pub fn path(input: TokenStream) -> TokenStream {
+    let pattern = parse_macro_input!(input as LitStr);
+
+    match check_pattern(&pattern.value()) {
+        Ok(()) => quote!(#pattern).into(),
+        Err(message) => syn::Error::new(pattern.span(), message)
+            .to_compile_error()
+            .into(),
+    }
+}
+
+fn check_pattern(pattern: &str) -> Result<(), String> {
+    let segments = pattern.split('/').collect::<Vec<_>>();
+    let mut names = Vec::new();
+
+    for (i, segment) in segments.iter().enumerate() {
+        let name = match segment.chars().next() {
+            Some(':') => &segment[1..],
+            Some('*') if i + 1 < segments.len() => {
+                return Err(format!("catch-all `{}` must be the last segment", segment));
+            }
+            Some('*') => match &segment[1..] {
+                "" => continue,
+                name => name,
+            },
+            Some('{') if segment.ends_with('}') && segment.len() > 1 => {
+                let inner = &segment[1..segment.len() - 1];
+                match inner.split_once(':') {
+                    Some((name, ty)) => {
+                        if !["string", "int", "uuid", "slug"].contains(&ty) {
+                            return Err(format!("unknown parameter type `{}`", ty));
+                        }
+                        name
+                    }
+                    None => inner,
+                }
+            }
+            _ if segment.contains(['{', '}']) => {
+                return Err(format!("unbalanced braces in segment `{}`", segment));
+            }
+            _ => continue,
+        };
+
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("invalid parameter name in segment `{}`", segment));
+        }
+        if names.contains(&name) {
+            return Err(format!("duplicate parameter name `{}`", name));
+        }
+        names.push(name);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,84 @@
+//! Double-submit CSRF tokens, signed with HMAC-SHA256 in the cookie
+
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha256;
+
+/// The cookie holding the signed token
+pub(crate) const COOKIE: &str = "csrf_token";
+/// The request header submitting the token, e.g. from scripts
+pub(crate) const HEADER: &str = "x-csrf-token";
+/// The form field submitting the token
+pub(crate) const FIELD: &str = "csrf_token";
+
+/// A fresh token of 16 bytes from the OS random number generator, as hex
+pub(crate) fn token() -> anyhow::Result<String> {
+    let mut bytes = [0; 16];
+    getrandom::getrandom(&mut bytes)?;
+    Ok(hex(&bytes))
+}
+
+/// The cookie value, `token.signature`
+pub(crate) fn sign(key: &[u8], token: &str) -> String {
+    let mut mac = mac(key);
+    mac.update(token.as_bytes());
+    format!("{}.{}", token, hex(&mac.finalize().into_bytes()))
+}
+
+/// Checks the signature of the cookie value, returning its token
+pub(crate) fn verify<'a>(key: &[u8], value: &'a str) -> Option<&'a str> {
+    let (token, signature) = value.rsplit_once('.')?;
+    let signature = unhex(signature)?;
+
+    let mut mac = mac(key);
+    mac.update(token.as_bytes());
+    mac.verify(&signature).ok()?;
+    Some(token)
+}
+
+/// Compares in time independent of where the tokens differ
+pub(crate) fn eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |d, (a, b)| d | (a ^ b)) == 0
+}
+
+fn mac(key: &[u8]) -> Hmac<Sha256> {
+    Hmac::new_from_slice(key).expect("HMAC accepts keys of any length")
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn sign_verify() -> anyhow::Result<()> {
+        let token = super::token()?;
+        assert_eq!(token.len(), 32);
+        assert_ne!(token, super::token()?);
+
+        let value = super::sign(b"secret", &token);
+        assert_eq!(super::verify(b"secret", &value), Some(token.as_str()));
+        assert_eq!(super::verify(b"other", &value), None);
+        let mut tampered = value.clone();
+        let last = if tampered.pop() == Some('0') {
+            '1'
+        } else {
+            '0'
+        };
+        tampered.push(last);
+        assert_eq!(super::verify(b"secret", &tampered), None);
+        assert_eq!(super::verify(b"secret", &token), None);
+
+        Ok(())
+    }
+}
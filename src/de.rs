@@ -0,0 +1,116 @@
+use serde::de::{self, value::Error, Deserializer, IntoDeserializer, Visitor};
+
+/// Deserializes a textual value, parsing it into whatever type the target asks for
+pub(crate) struct Value<'de> {
+    value: &'de str,
+    int: bool,
+    lenient: bool,
+}
+
+impl<'de> Value<'de> {
+    /// `int` hints that `deserialize_any` should produce an integer
+    pub(crate) fn new(value: &'de str, int: bool) -> Self {
+        Self {
+            value,
+            int,
+            lenient: false,
+        }
+    }
+
+    /// Also accepts `yes`/`no`, `on`/`off` and `1`/`0` as bools, in any case
+    pub(crate) fn lenient(mut self) -> Self {
+        self.lenient = true;
+        self
+    }
+
+    fn parse<T: std::str::FromStr>(&self, expected: &str) -> Result<T, Error> {
+        self.value
+            .parse()
+            .map_err(|_| de::Error::invalid_value(de::Unexpected::Str(self.value), &expected))
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                visitor.$visit(self.parse(stringify!($visit))?)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Value<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.int {
+            if let Ok(n) = self.value.parse::<u64>() {
+                return visitor.visit_u64(n);
+            }
+        }
+        visitor.visit_borrowed_str(self.value)
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.lenient {
+            const TRUE: [&str; 4] = ["true", "yes", "on", "1"];
+            const FALSE: [&str; 4] = ["false", "no", "off", "0"];
+
+            if TRUE.iter().any(|t| t.eq_ignore_ascii_case(self.value)) {
+                return visitor.visit_bool(true);
+            }
+            if FALSE.iter().any(|t| t.eq_ignore_ascii_case(self.value)) {
+                return visitor.visit_bool(false);
+            }
+        }
+        visitor.visit_bool(self.parse("visit_bool")?)
+    }
+
+    deserialize_parsed! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_enum(self.value.into_deserializer())
+    }
+
+    serde::forward_to_deserialize_any! {
+        char str string bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
@@ -0,0 +1,394 @@
+use crate::{anyhow::Result, Error};
+use brotli::{BrotliDecompressStream, BrotliResult, BrotliState, HeapAlloc, HuffmanCode};
+use bytes::{Buf, Bytes, BytesMut};
+use flate2::{Crc, Decompress, FlushDecompress, Status};
+use std::io;
+
+/// The most bytes a decoder inflates at a time
+pub(crate) const CHUNK: usize = 8 * 1024;
+
+type Brotli = BrotliState<HeapAlloc<u8>, HeapAlloc<u32>, HeapAlloc<HuffmanCode>>;
+
+/// An incremental decoder for a `Content-Encoding`, inflating into a bounded buffer
+pub(crate) enum Decoder {
+    Gzip(Gzip),
+    Deflate(Box<Decompress>),
+    Brotli(Box<Brotli>),
+}
+
+/// The parts of a gzip member (RFC 1952), around its raw deflate data
+pub(crate) enum Gzip {
+    Header(Vec<u8>),
+    Body(Box<Decompress>, Crc),
+    Trailer(Vec<u8>, Crc),
+    Done,
+}
+
+/// What a call to [`Decoder::decode`] did
+struct Progress {
+    read: usize,
+    written: usize,
+    done: bool,
+}
+
+impl Decoder {
+    /// Returns `None` for an unsupported encoding
+    pub(crate) fn new(encoding: &str) -> Option<Self> {
+        match encoding.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Some(Self::Gzip(Gzip::Header(Vec::new()))),
+            "deflate" => Some(Self::Deflate(Box::new(Decompress::new(true)))),
+            "br" => Some(Self::Brotli(Box::new(BrotliState::new(
+                HeapAlloc::new(0),
+                HeapAlloc::new(0),
+                HeapAlloc::new(HuffmanCode::default()),
+            )))),
+            _ => None,
+        }
+    }
+
+    /// Inflates from the start of `input` into `out`, ignoring any input past the end of
+    /// the stream
+    fn decode(&mut self, input: &[u8], out: &mut [u8]) -> io::Result<Progress> {
+        match self {
+            Self::Gzip(gzip) => gzip.decode(input, out),
+            Self::Deflate(d) => inflate(d, input, out),
+            Self::Brotli(state) => {
+                let (mut available_in, mut read) = (input.len(), 0);
+                let (mut available_out, mut written, mut total) = (out.len(), 0, 0);
+                let result = BrotliDecompressStream(
+                    &mut available_in,
+                    &mut read,
+                    input,
+                    &mut available_out,
+                    &mut written,
+                    out,
+                    &mut total,
+                    state,
+                );
+                match result {
+                    BrotliResult::ResultFailure => Err(invalid("corrupt brotli stream")),
+                    result => Ok(Progress {
+                        read,
+                        written,
+                        done: matches!(result, BrotliResult::ResultSuccess),
+                    }),
+                }
+            }
+        }
+    }
+}
+
+impl Gzip {
+    fn decode(&mut self, input: &[u8], out: &mut [u8]) -> io::Result<Progress> {
+        let mut progress = Progress {
+            read: input.len(),
+            written: 0,
+            done: false,
+        };
+
+        match self {
+            Self::Header(header) => {
+                let before = header.len();
+                header.extend_from_slice(input);
+                if let Some(len) = header_len(header)? {
+                    progress.read = len - before;
+                    *self = Self::Body(Box::new(Decompress::new(false)), Crc::new());
+                }
+            }
+            Self::Body(d, crc) => {
+                progress = inflate(d, input, out)?;
+                crc.update(&out[..progress.written]);
+                if progress.done {
+                    progress.done = false;
+                    if let Self::Body(_, crc) = std::mem::replace(self, Self::Done) {
+                        *self = Self::Trailer(Vec::new(), crc);
+                    }
+                }
+            }
+            Self::Trailer(trailer, crc) => {
+                progress.read = input.len().min(8 - trailer.len());
+                trailer.extend_from_slice(&input[..progress.read]);
+                if trailer.len() == 8 {
+                    let word = |at: usize| {
+                        u32::from_le_bytes([
+                            trailer[at],
+                            trailer[at + 1],
+                            trailer[at + 2],
+                            trailer[at + 3],
+                        ])
+                    };
+                    if word(0) != crc.sum() || word(4) != crc.amount() {
+                        return Err(invalid("gzip checksum mismatch"));
+                    }
+                    *self = Self::Done;
+                    progress.done = true;
+                }
+            }
+            Self::Done => progress.done = true,
+        }
+
+        Ok(progress)
+    }
+}
+
+/// The length of the gzip header at the start of `buf`, `None` while it is incomplete
+fn header_len(buf: &[u8]) -> io::Result<Option<usize>> {
+    let magic = [0x1f, 0x8b, 8];
+    if buf.iter().zip(&magic).any(|(a, b)| a != b) {
+        return Err(invalid("invalid gzip header"));
+    }
+    if buf.len() < 10 {
+        return Ok(None);
+    }
+
+    let flags = buf[3];
+    let mut len = 10;
+    // FEXTRA
+    if flags & 4 != 0 {
+        match buf.get(len..len + 2) {
+            Some(n) => len += 2 + u16::from_le_bytes([n[0], n[1]]) as usize,
+            None => return Ok(None),
+        }
+    }
+    // FNAME and FCOMMENT, zero-terminated
+    for flag in [8, 16] {
+        if flags & flag != 0 {
+            match buf.get(len..).and_then(|s| s.iter().position(|b| *b == 0)) {
+                Some(end) => len += end + 1,
+                None => return Ok(None),
+            }
+        }
+    }
+    // FHCRC
+    if flags & 2 != 0 {
+        len += 2;
+    }
+
+    Ok((buf.len() >= len).then_some(len))
+}
+
+fn inflate(d: &mut Decompress, input: &[u8], out: &mut [u8]) -> io::Result<Progress> {
+    let (total_in, total_out) = (d.total_in(), d.total_out());
+    let status = d
+        .decompress(input, out, FlushDecompress::None)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(Progress {
+        read: (d.total_in() - total_in) as usize,
+        written: (d.total_out() - total_out) as usize,
+        done: status == Status::StreamEnd,
+    })
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// A decoder with the input it has not read yet
+struct Stage {
+    decoder: Decoder,
+    input: Bytes,
+    /// No more input will come
+    eof: bool,
+    done: bool,
+}
+
+/// The decoders undoing a `Content-Encoding` list, last applied first, each inflating at
+/// most [`CHUNK`] bytes at a time
+pub(crate) struct Decoders {
+    stages: Vec<Stage>,
+    /// The bytes left to inflate before failing with [`Error::PayloadTooLarge`]
+    remaining: usize,
+}
+
+impl Decoders {
+    /// Parses the codings, skipping `identity`, and fails with
+    /// [`Error::UnsupportedMediaType`] for an unsupported one
+    pub(crate) fn new<'a>(codings: impl Iterator<Item = &'a str>, limit: usize) -> Result<Self> {
+        let mut stages = Vec::new();
+        for coding in codings.flat_map(|c| c.split(',')).map(str::trim) {
+            if coding.is_empty() || coding.eq_ignore_ascii_case("identity") {
+                continue;
+            }
+            stages.push(Stage {
+                decoder: Decoder::new(coding).ok_or(Error::UnsupportedMediaType)?,
+                input: Bytes::new(),
+                eof: false,
+                done: false,
+            });
+        }
+        stages.reverse();
+        Ok(Self {
+            stages,
+            remaining: limit,
+        })
+    }
+
+    /// Whether the body is sent as is
+    pub(crate) fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// Whether the last decoder reached the end of its stream
+    pub(crate) fn is_done(&self) -> bool {
+        self.stages.last().is_none_or(|s| s.done)
+    }
+
+    /// Queues a chunk of the encoded body
+    pub(crate) fn push(&mut self, chunk: Bytes) {
+        if let Some(stage) = self.stages.first_mut() {
+            append(&mut stage.input, chunk);
+        }
+    }
+
+    /// Marks the end of the encoded body
+    pub(crate) fn close(&mut self) {
+        if let Some(stage) = self.stages.first_mut() {
+            stage.eof = true;
+        }
+    }
+
+    /// The next inflated bytes, `None` when the decoders need another chunk or are done.
+    ///
+    /// Fails with [`Error::PayloadTooLarge`] as soon as the inflated bytes pass the limit.
+    pub(crate) fn next(&mut self) -> Result<Option<Bytes>> {
+        let last = match self.stages.len().checked_sub(1) {
+            Some(last) => last,
+            None => return Ok(None),
+        };
+        let chunk = self.pull(last)?;
+        if let Some(chunk) = &chunk {
+            self.remaining = self
+                .remaining
+                .checked_sub(chunk.len())
+                .ok_or(Error::PayloadTooLarge)?;
+        }
+        Ok(chunk)
+    }
+
+    /// The next output of the decoder at `i`, feeding it from the ones before it
+    fn pull(&mut self, i: usize) -> io::Result<Option<Bytes>> {
+        let mut out = [0; CHUNK];
+        loop {
+            let stage = &mut self.stages[i];
+            if stage.done {
+                return Ok(None);
+            }
+
+            let progress = stage.decoder.decode(&stage.input, &mut out)?;
+            stage.input.advance(progress.read);
+            stage.done = progress.done;
+            if progress.written > 0 {
+                return Ok(Some(Bytes::copy_from_slice(&out[..progress.written])));
+            }
+            if progress.read > 0 || progress.done {
+                continue;
+            }
+
+            // no progress without more input
+            if stage.eof {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "truncated encoded stream",
+                ));
+            }
+            if i == 0 {
+                return Ok(None);
+            }
+            match self.pull(i - 1)? {
+                Some(chunk) => append(&mut self.stages[i].input, chunk),
+                None if self.stages[i - 1].done => self.stages[i].eof = true,
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+fn append(input: &mut Bytes, chunk: Bytes) {
+    if input.is_empty() {
+        *input = chunk;
+    } else {
+        let mut buf = BytesMut::with_capacity(input.len() + chunk.len());
+        buf.extend_from_slice(input);
+        buf.extend_from_slice(&chunk);
+        *input = buf.freeze();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Decoders, CHUNK};
+    use crate::Error;
+    use std::io::Write;
+
+    fn decode(decoders: &mut Decoders, encoded: &[u8], size: usize) -> anyhow::Result<Vec<u8>> {
+        let mut decoded = Vec::new();
+        let mut chunks = encoded.chunks(size);
+        loop {
+            match decoders.next()? {
+                Some(chunk) => {
+                    assert!(chunk.len() <= CHUNK);
+                    decoded.extend_from_slice(&chunk);
+                }
+                None if decoders.is_done() => return Ok(decoded),
+                None => match chunks.next() {
+                    Some(chunk) => decoders.push(chunk.to_vec().into()),
+                    None => decoders.close(),
+                },
+            }
+        }
+    }
+
+    #[test]
+    fn stacked() -> anyhow::Result<()> {
+        let data = b"hello world\n".repeat(4096);
+
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        gzip.write_all(&data)?;
+        let mut deflate = flate2::write::ZlibEncoder::new(Vec::new(), Default::default());
+        deflate.write_all(&gzip.finish()?)?;
+        let mut br = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+        br.write_all(&deflate.finish()?)?;
+        let encoded = br.into_inner();
+
+        let codings = || vec!["gzip, identity", "deflate,br"].into_iter();
+        let mut decoders = Decoders::new(codings(), usize::MAX)?;
+        assert_eq!(decode(&mut decoders, &encoded, 7)?, data);
+
+        assert!(Decoders::new(vec!["identity"].into_iter(), 0)?.is_empty());
+        let err = Decoders::new(vec!["gzip, zstd"].into_iter(), 0)
+            .err()
+            .unwrap();
+        assert_eq!(err.downcast_ref(), Some(&Error::UnsupportedMediaType));
+
+        let mut decoders = Decoders::new(vec!["br"].into_iter(), usize::MAX)?;
+        assert!(decode(&mut decoders, &encoded[..encoded.len() / 2], 64).is_err());
+
+        let mut decoders = Decoders::new(codings(), data.len() - 1)?;
+        let err = decode(&mut decoders, &encoded, 64).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&Error::PayloadTooLarge));
+
+        Ok(())
+    }
+
+    #[test]
+    fn gzip_header() -> anyhow::Result<()> {
+        let mut gzip = flate2::GzBuilder::new()
+            .filename("data.txt")
+            .comment("a comment")
+            .extra(vec![1, 2, 3])
+            .write(Vec::new(), Default::default());
+        gzip.write_all(b"hello world\n")?;
+        let encoded = gzip.finish()?;
+
+        let mut decoders = Decoders::new(vec!["gzip"].into_iter(), usize::MAX)?;
+        assert_eq!(decode(&mut decoders, &encoded, 1)?, b"hello world\n");
+
+        let mut corrupted = encoded.clone();
+        let at = corrupted.len() - 8;
+        corrupted[at] ^= 1;
+        let mut decoders = Decoders::new(vec!["gzip"].into_iter(), usize::MAX)?;
+        assert!(decode(&mut decoders, &corrupted, 16).is_err());
+
+        Ok(())
+    }
+}
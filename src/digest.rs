@@ -0,0 +1,39 @@
+use sha2::{Digest, Sha256, Sha512};
+
+/// Verifies the body against a `Content-Digest` or `Repr-Digest` dictionary (RFC 9530),
+/// e.g. `sha-256=:RK/0qy18MlBSVnWgjwz6lZEWjP/lF5HF9bvEF8FabDg=:`.
+///
+/// Returns `None` when the dictionary has no supported algorithm, otherwise whether every
+/// supported digest matches.
+pub(crate) fn verify(dictionary: &str, body: &[u8]) -> Option<bool> {
+    let mut verified = None;
+
+    for member in dictionary.split(',') {
+        let (algorithm, value) = match member.split_once('=') {
+            Some((k, v)) => (k.trim(), v.split(';').next().unwrap_or_default().trim()),
+            None => continue,
+        };
+
+        let actual = match algorithm {
+            "sha-256" => Sha256::digest(body).to_vec(),
+            "sha-512" => Sha512::digest(body).to_vec(),
+            _ => continue,
+        };
+
+        let expected = match value
+            .strip_prefix(':')
+            .and_then(|v| v.strip_suffix(':'))
+            .and_then(|v| base64::decode(v).ok())
+        {
+            Some(expected) => expected,
+            None => return Some(false),
+        };
+
+        if actual != expected {
+            return Some(false);
+        }
+        verified = Some(true);
+    }
+
+    verified
+}
@@ -0,0 +1,172 @@
+use crate::Encoding;
+use bytes::Bytes;
+use flate2::write::{GzEncoder, ZlibEncoder};
+use futures_util::stream::{self, Stream, StreamExt};
+use std::{
+    error::Error as StdError,
+    io::{self, Write},
+};
+
+type BoxError = Box<dyn StdError + Send + Sync>;
+
+/// Which responses [`ResponseExt::compress_with`](crate::ResponseExt::compress_with)
+/// compresses.
+///
+/// By default bodies of a known size under 1 KiB are skipped, as are media types already
+/// compressed: raster images, audio, video, WOFF fonts and archives.
+#[derive(Clone, Debug)]
+pub struct Compression {
+    min_size: u64,
+    skip: Vec<mime::Mime>,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        let skip = [
+            "image/png",
+            "image/jpeg",
+            "image/gif",
+            "image/webp",
+            "image/avif",
+            "image/heic",
+            "audio/*",
+            "video/*",
+            "font/woff",
+            "font/woff2",
+            "application/zip",
+            "application/gzip",
+            "application/x-gzip",
+            "application/x-bzip2",
+            "application/x-xz",
+            "application/zstd",
+            "application/x-7z-compressed",
+            "application/x-rar-compressed",
+            "application/vnd.rar",
+        ];
+
+        Self {
+            min_size: 1024,
+            skip: skip.iter().map(|m| m.parse().unwrap()).collect(),
+        }
+    }
+}
+
+impl Compression {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Skips bodies of a known size under `size` bytes
+    pub fn min_size(mut self, size: u64) -> Self {
+        self.min_size = size;
+        self
+    }
+
+    /// Never compresses the media type, or the types of a `type/*` range
+    pub fn skip(mut self, mime: mime::Mime) -> Self {
+        self.skip.push(mime);
+        self
+    }
+
+    /// Forgets the skipped media types, the defaults included
+    pub fn skip_none(mut self) -> Self {
+        self.skip.clear();
+        self
+    }
+
+    /// Whether a body of the media type and size is worth compressing
+    pub(crate) fn applies(&self, mime: Option<&mime::Mime>, size: Option<u64>) -> bool {
+        let skipped = mime.is_some_and(|m| {
+            self.skip.iter().any(|s| {
+                s.type_() == m.type_() && (s.subtype() == mime::STAR || s.subtype() == m.subtype())
+            })
+        });
+        !skipped && size.is_none_or(|n| n >= self.min_size)
+    }
+}
+
+/// An incremental encoder for a `Content-Encoding`
+pub(crate) enum Encoder {
+    Gzip(GzEncoder<Vec<u8>>),
+    Deflate(ZlibEncoder<Vec<u8>>),
+    Brotli(Box<brotli::CompressorWriter<Vec<u8>>>),
+}
+
+impl Encoder {
+    /// Returns `None` for identity and unsupported encodings
+    pub(crate) fn new(encoding: Encoding) -> Option<Self> {
+        match encoding {
+            Encoding::Gzip => Some(Self::Gzip(GzEncoder::new(
+                Vec::new(),
+                flate2::Compression::fast(),
+            ))),
+            Encoding::Deflate => Some(Self::Deflate(ZlibEncoder::new(
+                Vec::new(),
+                flate2::Compression::fast(),
+            ))),
+            Encoding::Brotli => Some(Self::Brotli(Box::new(brotli::CompressorWriter::new(
+                Vec::new(),
+                4096,
+                4,
+                22,
+            )))),
+            Encoding::Identity => None,
+        }
+    }
+
+    /// Feeds a chunk and flushes, returning the bytes deflated so far, so the peer can
+    /// inflate everything sent up to this chunk
+    pub(crate) fn encode(&mut self, chunk: &[u8]) -> io::Result<Bytes> {
+        let buf = match self {
+            Self::Gzip(e) => {
+                e.write_all(chunk)?;
+                e.flush()?;
+                e.get_mut()
+            }
+            Self::Deflate(e) => {
+                e.write_all(chunk)?;
+                e.flush()?;
+                e.get_mut()
+            }
+            Self::Brotli(e) => {
+                e.write_all(chunk)?;
+                e.flush()?;
+                e.get_mut()
+            }
+        };
+        Ok(std::mem::take(buf).into())
+    }
+
+    /// Finishes the stream, returning the remaining bytes
+    pub(crate) fn finish(self) -> io::Result<Bytes> {
+        match self {
+            Self::Gzip(e) => e.finish(),
+            Self::Deflate(e) => e.finish(),
+            Self::Brotli(e) => Ok(e.into_inner()),
+        }
+        .map(Into::into)
+    }
+}
+
+/// Encodes the chunks of the stream as they arrive
+pub(crate) fn encode_stream<S, E>(
+    body: S,
+    encoder: Encoder,
+) -> impl Stream<Item = Result<Bytes, BoxError>> + Send + 'static
+where
+    S: Stream<Item = Result<Bytes, E>> + Send + 'static,
+    E: Into<BoxError> + 'static,
+{
+    stream::unfold(Some((body.boxed(), encoder)), |state| async move {
+        let (mut body, mut encoder) = state?;
+        match body.next().await {
+            Some(Ok(chunk)) => Some((
+                encoder.encode(&chunk).map_err(Into::into),
+                Some((body, encoder)),
+            )),
+            Some(Err(e)) => Some((Err(e.into()), None)),
+            None => Some((encoder.finish().map_err(Into::into), None)),
+        }
+    })
+    .filter(|r| futures_util::future::ready(!matches!(r, Ok(b) if b.is_empty())))
+}
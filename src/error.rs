@@ -0,0 +1,58 @@
+use crate::{Body, Response, StatusCode};
+use std::fmt;
+
+/// Errors which map to a specific response status
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// `400 Bad Request`, the body lacks or does not match its `Content-Digest` or
+    /// `Repr-Digest`
+    DigestMismatch,
+    /// `401 Unauthorized`
+    Unauthorized,
+    /// `406 Not Acceptable`
+    NotAcceptable,
+    /// `408 Request Timeout`
+    Timeout,
+    /// `413 Payload Too Large`
+    PayloadTooLarge,
+    /// `415 Unsupported Media Type`
+    UnsupportedMediaType,
+    /// `416 Range Not Satisfiable`
+    RangeNotSatisfiable,
+    /// `417 Expectation Failed`
+    ExpectationFailed,
+    /// `505 HTTP Version Not Supported`
+    HttpVersionNotSupported,
+}
+
+impl Error {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::DigestMismatch => StatusCode::BAD_REQUEST,
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::NotAcceptable => StatusCode::NOT_ACCEPTABLE,
+            Self::Timeout => StatusCode::REQUEST_TIMEOUT,
+            Self::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Self::RangeNotSatisfiable => StatusCode::RANGE_NOT_SATISFIABLE,
+            Self::ExpectationFailed => StatusCode::EXPECTATION_FAILED,
+            Self::HttpVersionNotSupported => StatusCode::HTTP_VERSION_NOT_SUPPORTED,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.status().canonical_reason().unwrap_or_default())
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for Response<Body> {
+    fn from(e: Error) -> Self {
+        let mut res = Response::new(Body::from(e.to_string()));
+        *res.status_mut() = e.status();
+        res
+    }
+}
@@ -0,0 +1,134 @@
+//! Entity tag comparison of conditional requests (RFC 7232)
+
+/// An entity tag, `"opaque"` or weak `W/"opaque"`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ETag<'a> {
+    weak: bool,
+    opaque: &'a str,
+}
+
+impl<'a> ETag<'a> {
+    pub(crate) fn parse(s: &'a str) -> Option<Self> {
+        match parse_list(s.trim())?.as_slice() {
+            [tag] => Some(*tag),
+            _ => None,
+        }
+    }
+
+    /// Both tags are strong and their opaque tags are equal, as If-Match requires
+    pub(crate) fn strong_eq(&self, other: &ETag<'_>) -> bool {
+        !self.weak && !other.weak && self.opaque == other.opaque
+    }
+
+    /// The opaque tags are equal, regardless of weakness, as If-None-Match requires
+    pub(crate) fn weak_eq(&self, other: &ETag<'_>) -> bool {
+        self.opaque == other.opaque
+    }
+}
+
+/// The value of an `If-Match` or `If-None-Match` header
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Condition<'a> {
+    /// `*`, any current representation
+    Any,
+    Tags(Vec<ETag<'a>>),
+}
+
+impl<'a> Condition<'a> {
+    /// Returns `None` when the header is malformed
+    pub(crate) fn parse(s: &'a str) -> Option<Self> {
+        match s.trim() {
+            "*" => Some(Self::Any),
+            s => parse_list(s).map(Self::Tags),
+        }
+    }
+
+    /// Evaluates the condition against the current tag, `None` when there is no current
+    /// representation
+    pub(crate) fn matches(
+        &self,
+        current: Option<&ETag<'_>>,
+        eq: impl Fn(&ETag<'a>, &ETag<'_>) -> bool,
+    ) -> bool {
+        match (self, current) {
+            (_, None) => false,
+            (Self::Any, Some(_)) => true,
+            (Self::Tags(tags), Some(current)) => tags.iter().any(|tag| eq(tag, current)),
+        }
+    }
+}
+
+fn parse_list(s: &str) -> Option<Vec<ETag<'_>>> {
+    let mut tags = Vec::new();
+    let mut rest = s;
+
+    loop {
+        rest = rest.trim_start_matches([',', ' ', '\t']);
+        if rest.is_empty() {
+            break;
+        }
+
+        let weak = rest.starts_with("W/");
+        if weak {
+            rest = &rest[2..];
+        }
+
+        rest = rest.strip_prefix('"')?;
+        let end = rest.find('"')?;
+        let opaque = &rest[..end];
+        if !opaque
+            .bytes()
+            .all(|b| b == 0x21 || (0x23..=0x7e).contains(&b) || b >= 0x80)
+        {
+            return None;
+        }
+        tags.push(ETag { weak, opaque });
+
+        rest = rest[end + 1..].trim_start_matches([' ', '\t']);
+        if !rest.is_empty() && !rest.starts_with(',') {
+            return None;
+        }
+    }
+
+    Some(tags).filter(|tags| !tags.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Condition, ETag};
+
+    #[test]
+    fn comparison() {
+        let strong = ETag::parse("\"x\"").unwrap();
+        let weak = ETag::parse("W/\"x\"").unwrap();
+        let other = ETag::parse("\"y\"").unwrap();
+
+        assert!(strong.strong_eq(&strong));
+        assert!(!strong.strong_eq(&weak));
+        assert!(!weak.strong_eq(&weak));
+        assert!(!strong.strong_eq(&other));
+
+        assert!(strong.weak_eq(&weak));
+        assert!(weak.weak_eq(&weak));
+        assert!(!weak.weak_eq(&other));
+
+        assert_eq!(ETag::parse("x"), None);
+        assert_eq!(ETag::parse("\"x\", \"y\""), None);
+    }
+
+    #[test]
+    fn condition() {
+        let current = ETag::parse("W/\"x\"").unwrap();
+
+        let any = Condition::parse(" * ").unwrap();
+        assert!(any.matches(Some(&current), ETag::strong_eq));
+        assert!(!any.matches(None, ETag::weak_eq));
+
+        let tags = Condition::parse("\"a\", W/\"x\",\"b,c\"").unwrap();
+        assert!(tags.matches(Some(&current), ETag::weak_eq));
+        assert!(!tags.matches(Some(&current), ETag::strong_eq));
+
+        assert_eq!(Condition::parse("\"a\" \"b\""), None);
+        assert_eq!(Condition::parse("\"a"), None);
+    }
+}
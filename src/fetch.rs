@@ -0,0 +1,142 @@
+use crate::Method;
+
+/// The `Sec-Fetch-*` request metadata sent by browsers
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FetchMetadata {
+    /// `Sec-Fetch-Site`
+    pub site: Option<FetchSite>,
+    /// `Sec-Fetch-Mode`
+    pub mode: Option<FetchMode>,
+    /// `Sec-Fetch-Dest`
+    pub dest: Option<FetchDest>,
+    /// `Sec-Fetch-User: ?1`, the navigation was triggered by the user
+    pub user: bool,
+}
+
+impl FetchMetadata {
+    /// The request came from the same site, or was initiated by the user directly, e.g.
+    /// from the address bar
+    pub fn is_same_site(&self) -> bool {
+        matches!(
+            self.site,
+            Some(FetchSite::SameOrigin) | Some(FetchSite::SameSite) | Some(FetchSite::None)
+        )
+    }
+
+    pub fn is_same_origin(&self) -> bool {
+        self.site == Some(FetchSite::SameOrigin)
+    }
+
+    pub fn is_cross_site(&self) -> bool {
+        self.site == Some(FetchSite::CrossSite)
+    }
+
+    /// Whether a resource isolation policy lets the request through: requests of browsers
+    /// which send no metadata, same-site requests, and cross-site top-level `GET`
+    /// navigations that do not embed the resource.
+    pub fn resource_isolation_allows(&self, method: &Method) -> bool {
+        if self.site.is_none() || self.is_same_site() {
+            return true;
+        }
+
+        self.mode == Some(FetchMode::Navigate)
+            && method == Method::GET
+            && !matches!(self.dest, Some(FetchDest::Object) | Some(FetchDest::Embed))
+    }
+}
+
+/// The values of `Sec-Fetch-Site`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FetchSite {
+    CrossSite,
+    SameOrigin,
+    SameSite,
+    /// Initiated by the user rather than a site
+    None,
+}
+
+impl FetchSite {
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        match s {
+            "cross-site" => Some(Self::CrossSite),
+            "same-origin" => Some(Self::SameOrigin),
+            "same-site" => Some(Self::SameSite),
+            "none" => Some(Self::None),
+            _ => None,
+        }
+    }
+}
+
+/// The values of `Sec-Fetch-Mode`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FetchMode {
+    Cors,
+    Navigate,
+    NoCors,
+    SameOrigin,
+    WebSocket,
+}
+
+impl FetchMode {
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        match s {
+            "cors" => Some(Self::Cors),
+            "navigate" => Some(Self::Navigate),
+            "no-cors" => Some(Self::NoCors),
+            "same-origin" => Some(Self::SameOrigin),
+            "websocket" => Some(Self::WebSocket),
+            _ => None,
+        }
+    }
+}
+
+/// The values of `Sec-Fetch-Dest`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FetchDest {
+    Audio,
+    Document,
+    Embed,
+    Empty,
+    Font,
+    Frame,
+    Iframe,
+    Image,
+    Manifest,
+    Object,
+    Report,
+    Script,
+    ServiceWorker,
+    SharedWorker,
+    Style,
+    Track,
+    Video,
+    Worker,
+    /// A destination not listed above, e.g. `audioworklet`
+    Other(String),
+}
+
+impl FetchDest {
+    pub(crate) fn parse(s: &str) -> Self {
+        match s {
+            "audio" => Self::Audio,
+            "document" => Self::Document,
+            "embed" => Self::Embed,
+            "empty" => Self::Empty,
+            "font" => Self::Font,
+            "frame" => Self::Frame,
+            "iframe" => Self::Iframe,
+            "image" => Self::Image,
+            "manifest" => Self::Manifest,
+            "object" => Self::Object,
+            "report" => Self::Report,
+            "script" => Self::Script,
+            "serviceworker" => Self::ServiceWorker,
+            "sharedworker" => Self::SharedWorker,
+            "style" => Self::Style,
+            "track" => Self::Track,
+            "video" => Self::Video,
+            "worker" => Self::Worker,
+            s => Self::Other(s.to_owned()),
+        }
+    }
+}
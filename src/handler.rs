@@ -0,0 +1,169 @@
+use crate::{anyhow::Result, async_trait, header, Body, Request, RequestExt, Response};
+use std::{error::Error as StdError, fmt, future::Future, ops::Deref, sync::Arc};
+use tower_service::Service;
+
+/// Handles a request and produces a response
+#[async_trait]
+pub trait Handler: Send + Sync + 'static {
+    async fn call(&self, req: Request<Body>) -> Result<Response<Body>>;
+}
+
+#[async_trait]
+impl<F, Fut> Handler for F
+where
+    F: Fn(Request<Body>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Response<Body>>> + Send + 'static,
+{
+    async fn call(&self, req: Request<Body>) -> Result<Response<Body>> {
+        (self)(req).await
+    }
+}
+
+/// Runs around a route's handler, e.g. for logging, auth or timing, see [`Router::with`].
+///
+/// Calling [`Next::run`] hands the request on to the remaining middleware and the handler;
+/// returning without calling it short-circuits them.
+///
+/// [`Router::with`]: crate::Router::with
+#[async_trait]
+pub trait Middleware: Send + Sync + 'static {
+    async fn call(&self, req: Request<Body>, next: Next) -> Result<Response<Body>>;
+}
+
+#[async_trait]
+impl<F, Fut> Middleware for F
+where
+    F: Fn(Request<Body>, Next) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Response<Body>>> + Send + 'static,
+{
+    async fn call(&self, req: Request<Body>, next: Next) -> Result<Response<Body>> {
+        (self)(req, next).await
+    }
+}
+
+/// The middleware left to run and the handler they wrap
+pub struct Next {
+    middleware: Arc<[Arc<dyn Middleware>]>,
+    index: usize,
+    handler: BoxHandler,
+}
+
+impl Next {
+    pub(crate) fn new(middleware: Arc<[Arc<dyn Middleware>]>, handler: BoxHandler) -> Self {
+        Self {
+            middleware,
+            index: 0,
+            handler,
+        }
+    }
+
+    /// Runs the next middleware, or the handler after the last one
+    pub async fn run(self, req: Request<Body>) -> Result<Response<Body>> {
+        match self.middleware.get(self.index).cloned() {
+            Some(middleware) => {
+                let next = Self {
+                    index: self.index + 1,
+                    ..self
+                };
+                middleware.call(req, next).await
+            }
+            None => self.handler.call(req).await,
+        }
+    }
+}
+
+impl fmt::Debug for Next {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Next")
+            .field("remaining", &(self.middleware.len() - self.index))
+            .finish()
+    }
+}
+
+/// A cloneable, type-erased [`Handler`]
+#[derive(Clone)]
+pub struct BoxHandler(Arc<dyn Handler>);
+
+impl BoxHandler {
+    pub fn new(handler: impl Handler) -> Self {
+        Self(Arc::new(handler))
+    }
+
+    /// Wraps a tower [`Service`], cloning it for each request
+    pub fn from_service<S>(service: S) -> Self
+    where
+        S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + Sync + 'static,
+        S::Error: Into<Box<dyn StdError + Send + Sync>>,
+        S::Future: Send,
+    {
+        Self::new(move |req: Request<Body>| {
+            let mut service = service.clone();
+            async move {
+                futures_util::future::poll_fn(|cx| service.poll_ready(cx))
+                    .await
+                    .map_err(|e| anyhow::anyhow!(e.into()))?;
+                service
+                    .call(req)
+                    .await
+                    .map_err(|e| anyhow::anyhow!(e.into()))
+            }
+        })
+    }
+}
+
+impl<H: Handler> From<H> for BoxHandler {
+    fn from(handler: H) -> Self {
+        Self::new(handler)
+    }
+}
+
+impl Deref for BoxHandler {
+    type Target = dyn Handler;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+impl fmt::Debug for BoxHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BoxHandler").finish()
+    }
+}
+
+/// Echoes a `TRACE` request back as a `message/http` body, as its final recipient.
+///
+/// Since the server answers instead of forwarding, `Max-Forwards` needs no decrementing,
+/// but a malformed value is rejected with `400`. The credentials carried by
+/// `Authorization`, `Proxy-Authorization` and `Cookie` are left out of the echo.
+pub async fn trace_echo(req: Request<Body>) -> Result<Response<Body>> {
+    if req.headers().contains_key(header::MAX_FORWARDS) && req.max_forwards().is_none() {
+        return Ok(Response::builder()
+            .status(crate::StatusCode::BAD_REQUEST)
+            .body(Body::empty())?);
+    }
+
+    let target = req
+        .uri()
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or("/");
+    let mut message = format!("{} {} {:?}\r\n", req.method(), target, req.version()).into_bytes();
+    for (name, value) in req.headers() {
+        if name == header::AUTHORIZATION
+            || name == header::PROXY_AUTHORIZATION
+            || name == header::COOKIE
+        {
+            continue;
+        }
+        message.extend_from_slice(name.as_str().as_bytes());
+        message.extend_from_slice(b": ");
+        message.extend_from_slice(value.as_bytes());
+        message.extend_from_slice(b"\r\n");
+    }
+    message.extend_from_slice(b"\r\n");
+
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "message/http")
+        .body(Body::from(message))?)
+}
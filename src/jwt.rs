@@ -0,0 +1,46 @@
+use crate::{anyhow::Result, Error};
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha256;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(serde::Deserialize)]
+struct Header {
+    alg: String,
+}
+
+#[derive(serde::Deserialize)]
+struct Registered {
+    exp: Option<u64>,
+    nbf: Option<u64>,
+}
+
+/// Verifies an HS256 token's signature, `exp` and `nbf`, returning its decoded payload
+pub(crate) fn verify(token: &str, key: &[u8]) -> Result<Vec<u8>> {
+    let mut parts = token.split('.');
+    let (header, payload, signature) =
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(h), Some(p), Some(s), None) => (h, p, s),
+            _ => return Err(Error::Unauthorized.into()),
+        };
+
+    let decode = |s: &str| base64::decode_config(s, base64::URL_SAFE_NO_PAD);
+
+    let alg = serde_json::from_slice::<Header>(&decode(header)?)?.alg;
+    anyhow::ensure!(alg == "HS256", Error::Unauthorized);
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).map_err(|_| Error::Unauthorized)?;
+    mac.update(header.as_bytes());
+    mac.update(b".");
+    mac.update(payload.as_bytes());
+    mac.verify(&decode(signature)?)
+        .map_err(|_| Error::Unauthorized)?;
+
+    let payload = decode(payload)?;
+    let claims = serde_json::from_slice::<Registered>(&payload)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+    anyhow::ensure!(claims.exp.is_none_or(|exp| now < exp), Error::Unauthorized);
+    anyhow::ensure!(claims.nbf.is_none_or(|nbf| nbf <= now), Error::Unauthorized);
+
+    Ok(payload)
+}
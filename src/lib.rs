@@ -1,10 +1,53 @@
-mod router;
+#[cfg(feature = "csrf")]
+mod csrf;
+#[cfg(feature = "serde")]
+mod de;
+#[cfg(feature = "decompress")]
+mod decode;
+#[cfg(feature = "digest")]
+mod digest;
+#[cfg(feature = "compress")]
+mod encode;
+mod error;
+mod etag;
+mod fetch;
+mod handler;
+#[cfg(feature = "jwt")]
+mod jwt;
+#[cfg(feature = "multipart")]
+mod multipart;
+mod negotiation;
+mod priority;
 mod request;
 mod response;
+mod router;
+mod security;
+mod shutdown;
+#[cfg(feature = "sse")]
+pub mod sse;
+pub mod structured;
 
 pub use anyhow;
 pub use async_trait::async_trait;
+#[cfg(feature = "compress")]
+pub use encode::Compression;
+pub use error::Error;
+pub use fetch::*;
+pub use handler::*;
+/// hyper's error, which [`Error`] shadows in the glob re-export of hyper
+pub use hyper::Error as HyperError;
 pub use hyper::*;
-pub use router::*;
+#[cfg(feature = "macros")]
+pub use hyperstone_macros::{path, route};
+#[cfg(feature = "macros")]
+#[doc(hidden)]
+pub use inventory;
+#[cfg(feature = "multipart")]
+pub use multipart::*;
+pub use negotiation::*;
+pub use priority::*;
 pub use request::*;
 pub use response::*;
+pub use router::*;
+pub use security::*;
+pub use shutdown::*;
@@ -0,0 +1,372 @@
+use crate::{anyhow::Result, async_trait, Body};
+#[cfg(feature = "serde")]
+use crate::{Error, Request, RequestExt};
+use bytes::Bytes;
+#[cfg(feature = "serde")]
+use bytes::BytesMut;
+#[cfg(feature = "serde")]
+use futures_util::StreamExt;
+#[cfg(feature = "serde")]
+use std::marker::PhantomData;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::SystemTime,
+};
+
+/// Extends the fields of a multipart body
+#[async_trait]
+pub trait FieldExt {
+    /// Reads and deserializes a JSON part, e.g. the metadata sent along with a file.
+    ///
+    /// Fails with [`Error`](crate::Error)`::UnsupportedMediaType` when the part's
+    /// `Content-Type` is not JSON.
+    #[cfg(feature = "json")]
+    async fn json<T>(&mut self) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned;
+}
+
+#[async_trait]
+impl FieldExt for form_data::Field<Body> {
+    #[cfg(feature = "json")]
+    async fn json<T>(&mut self) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let valid = self.content_type.as_ref().is_some_and(|m| {
+            m.type_() == mime::APPLICATION
+                && (m.subtype() == mime::JSON || m.suffix() == Some(mime::JSON))
+        });
+        anyhow::ensure!(valid, crate::Error::UnsupportedMediaType);
+
+        serde_json::from_slice(&self.bytes().await?).map_err(Into::into)
+    }
+}
+
+/// A file part of a `multipart/form-data` body
+#[derive(Clone, Debug)]
+pub struct FilePart {
+    pub name: String,
+    pub filename: Option<String>,
+    pub content_type: Option<mime::Mime>,
+    pub bytes: Bytes,
+}
+
+/// An uploaded file saved to a temporary path, removed when dropped unless persisted
+#[derive(Debug)]
+pub struct UploadedFile {
+    path: PathBuf,
+    pub filename: Option<String>,
+    pub content_type: Option<mime::Mime>,
+    persisted: bool,
+}
+
+impl UploadedFile {
+    /// Saves the field to a new file in the directory.
+    ///
+    /// The file is removed again if saving fails, or the future is dropped midway.
+    pub async fn save(field: &mut form_data::Field<Body>, dir: impl AsRef<Path>) -> Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.subsec_nanos());
+        let path = dir.as_ref().join(format!(
+            "hyperstone-upload-{}-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed),
+            nanos
+        ));
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+
+        let uploaded = Self {
+            path,
+            filename: field.filename.clone(),
+            content_type: field.content_type.clone(),
+            persisted: false,
+        };
+        field.copy_to_file(&mut file).await?;
+        Ok(uploaded)
+    }
+
+    /// The temporary path of the file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Moves the file to the path, keeping it past the drop
+    pub fn persist(mut self, to: impl AsRef<Path>) -> io::Result<PathBuf> {
+        let to = to.as_ref();
+        if fs::rename(&self.path, to).is_err() {
+            // Across file systems
+            fs::copy(&self.path, to)?;
+            fs::remove_file(&self.path)?;
+        }
+        self.persisted = true;
+        Ok(to.to_owned())
+    }
+}
+
+impl Drop for UploadedFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Reads a `multipart/form-data` body, deserializing its text fields into `T` and
+/// collecting its files apart.
+///
+/// Files larger than the file limit, or bodies whose parts add up to more than the total
+/// limit, fail with [`Error::PayloadTooLarge`].
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+pub struct MultipartForm<T> {
+    file_size: usize,
+    total_size: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+#[cfg(feature = "serde")]
+impl<T> Default for MultipartForm<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T> MultipartForm<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    /// Limits files to 8 MiB and the whole form to 32 MiB
+    pub fn new() -> Self {
+        Self {
+            file_size: 8 * 1024 * 1024,
+            total_size: 32 * 1024 * 1024,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn file_size(mut self, limit: usize) -> Self {
+        self.file_size = limit;
+        self
+    }
+
+    pub fn total_size(mut self, limit: usize) -> Self {
+        self.total_size = limit;
+        self
+    }
+
+    pub async fn extract(self, req: Request<Body>) -> Result<(T, Vec<FilePart>)> {
+        let mut form = req.multipart()?;
+        let mut fields = Vec::new();
+        let mut files = Vec::new();
+        let mut total = 0;
+
+        while let Some(field) = form.next().await {
+            let mut field = field?;
+            let limit = if field.filename.is_some() {
+                self.file_size
+            } else {
+                self.total_size
+            };
+
+            let mut bytes = BytesMut::new();
+            while let Some(chunk) = field.next().await {
+                let chunk = chunk?;
+                total += chunk.len();
+                if bytes.len() + chunk.len() > limit || total > self.total_size {
+                    return Err(Error::PayloadTooLarge.into());
+                }
+                bytes.extend_from_slice(&chunk);
+            }
+
+            if field.filename.is_some() {
+                files.push(FilePart {
+                    name: field.name.clone(),
+                    filename: field.filename.take(),
+                    content_type: field.content_type.take(),
+                    bytes: bytes.freeze(),
+                });
+            } else {
+                fields.push((field.name.clone(), String::from_utf8(bytes.to_vec())?));
+            }
+        }
+
+        let data =
+            T::deserialize(serde::de::value::MapDeserializer::new(fields.iter().map(
+                |(name, value)| (name.as_str(), crate::de::Value::new(value, false)),
+            )))?;
+
+        Ok((data, files))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UploadedFile;
+    use crate::{header, Body, Request, RequestExt};
+    use futures_util::StreamExt;
+
+    #[cfg(feature = "serde")]
+    #[derive(Debug, serde::Deserialize)]
+    struct Profile {
+        name: String,
+        age: u8,
+    }
+
+    fn req() -> Request<Body> {
+        let body = [
+            "--boundary",
+            "Content-Disposition: form-data; name=\"name\"",
+            "",
+            "Ferris",
+            "--boundary",
+            "Content-Disposition: form-data; name=\"avatar\"; filename=\"ferris.txt\"",
+            "Content-Type: text/plain",
+            "",
+            "crab crab crab",
+            "--boundary",
+            "Content-Disposition: form-data; name=\"age\"",
+            "",
+            "7",
+            "--boundary--",
+            "",
+        ]
+        .join("\r\n");
+
+        Request::post("/")
+            .header(
+                header::CONTENT_TYPE,
+                "multipart/form-data; boundary=boundary",
+            )
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn field_json() -> anyhow::Result<()> {
+        use super::FieldExt;
+        use crate::Error;
+
+        let body = [
+            "--boundary",
+            "Content-Disposition: form-data; name=\"metadata\"",
+            "Content-Type: application/json",
+            "",
+            "{\"name\":\"Ferris\",\"age\":7}",
+            "--boundary",
+            "Content-Disposition: form-data; name=\"avatar\"; filename=\"ferris.png\"",
+            "Content-Type: image/png",
+            "",
+            "\u{89}PNG",
+            "--boundary--",
+            "",
+        ]
+        .join("\r\n");
+        let req = Request::post("/")
+            .header(
+                header::CONTENT_TYPE,
+                "multipart/form-data; boundary=boundary",
+            )
+            .body(Body::from(body))?;
+        let mut form = req.multipart()?;
+
+        let mut metadata = form.next().await.unwrap()?;
+        let profile = metadata.json::<Profile>().await?;
+        assert_eq!(profile.name, "Ferris");
+        assert_eq!(profile.age, 7);
+
+        let mut avatar = form.next().await.unwrap()?;
+        let err = avatar.json::<Profile>().await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Error>(),
+            Some(&Error::UnsupportedMediaType)
+        );
+
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn multipart_form() -> anyhow::Result<()> {
+        use super::MultipartForm;
+        use crate::Error;
+
+        let (profile, files) = MultipartForm::<Profile>::new().extract(req()).await?;
+        assert_eq!(profile.name, "Ferris");
+        assert_eq!(profile.age, 7);
+
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].name, "avatar");
+        assert_eq!(files[0].filename.as_deref(), Some("ferris.txt"));
+        assert_eq!(files[0].content_type, Some(mime::TEXT_PLAIN));
+        assert_eq!(files[0].bytes, "crab crab crab");
+
+        let err = MultipartForm::<Profile>::new()
+            .file_size(4)
+            .extract(req())
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&Error::PayloadTooLarge));
+
+        let err = MultipartForm::<Profile>::new()
+            .total_size(16)
+            .extract(req())
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&Error::PayloadTooLarge));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn uploaded_file() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir();
+        let mut form = req().multipart()?;
+        let mut saved = Vec::new();
+
+        while let Some(field) = form.next().await {
+            let mut field = field?;
+            if field.filename.is_some() {
+                saved.push(UploadedFile::save(&mut field, &dir).await?);
+            } else {
+                field.ignore().await?;
+            }
+        }
+
+        let file = saved.pop().unwrap();
+        assert_eq!(file.filename.as_deref(), Some("ferris.txt"));
+        assert_eq!(std::fs::read_to_string(file.path())?, "crab crab crab");
+
+        let path = file.path().to_owned();
+        drop(file);
+        assert!(!path.exists());
+
+        let mut form = req().multipart()?;
+        let mut field = form.next().await.unwrap()?;
+        field.ignore().await?;
+        let mut field = form.next().await.unwrap()?;
+        let file = UploadedFile::save(&mut field, &dir).await?;
+        let temp = file.path().to_owned();
+
+        let to = dir.join("hyperstone-uploaded-file-persisted.txt");
+        assert_eq!(file.persist(&to)?, to);
+        assert!(!temp.exists());
+        assert_eq!(std::fs::read_to_string(&to)?, "crab crab crab");
+        std::fs::remove_file(&to)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+/// Parses a weighted list such as `Accept`, `Accept-Language`, `Accept-Encoding` or
+/// `Accept-Charset` into values paired with their quality, keeping the header's order.
+///
+/// Parameters other than `q` stay part of the value, e.g. `text/html;level=1`. A missing or
+/// malformed q-value counts as `1.0`, and q-values are clamped to `[0, 1]`.
+pub fn parse_quality_list(s: &str) -> Vec<(String, f32)> {
+    s.split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';').map(str::trim);
+            let mut value = parts.next().filter(|v| !v.is_empty())?.to_owned();
+            let mut quality = 1.0;
+
+            for param in parts {
+                match param.split_once('=') {
+                    Some((k, v)) if k.trim().eq_ignore_ascii_case("q") => {
+                        quality = v
+                            .trim()
+                            .parse::<f32>()
+                            .ok()
+                            .filter(|q| !q.is_nan())
+                            .map_or(1.0, |q| q.clamp(0.0, 1.0));
+                    }
+                    _ if !param.is_empty() => {
+                        value.push(';');
+                        value.push_str(param);
+                    }
+                    _ => {}
+                }
+            }
+
+            Some((value, quality))
+        })
+        .collect()
+}
+
+/// Picks the offered media type the `Accept` header prefers, in offered order on ties.
+///
+/// Each offered type takes the quality of the most specific range matching it, so that
+/// `text/*;q=0.5, text/plain` accepts `text/plain` fully. An absent header accepts anything.
+pub(crate) fn negotiate(accept: Option<&str>, offered: &[mime::Mime]) -> Option<mime::Mime> {
+    let ranges = match accept {
+        Some(accept) => parse_quality_list(accept)
+            .into_iter()
+            .filter_map(|(range, q)| range.parse::<mime::Mime>().ok().zip(Some(q)))
+            .collect::<Vec<_>>(),
+        None => return offered.first().cloned(),
+    };
+
+    offered
+        .iter()
+        .filter_map(|m| {
+            ranges
+                .iter()
+                .filter_map(|(range, q)| {
+                    let specificity = if range.type_() == mime::STAR {
+                        0
+                    } else if range.type_() != m.type_() {
+                        return None;
+                    } else if range.subtype() == mime::STAR {
+                        1
+                    } else if range.subtype() == m.subtype() {
+                        2
+                    } else {
+                        return None;
+                    };
+                    Some((specificity, *q))
+                })
+                .max_by_key(|(specificity, _)| *specificity)
+                .map(|(_, q)| (m, q))
+        })
+        .filter(|(_, q)| *q > 0.0)
+        .fold(
+            None,
+            |best: Option<(&mime::Mime, f32)>, (m, q)| match best {
+                Some((_, b)) if b >= q => best,
+                _ => Some((m, q)),
+            },
+        )
+        .map(|(m, _)| m.clone())
+}
+
+/// A content coding of a response body
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Identity,
+    Gzip,
+    Deflate,
+    Brotli,
+}
+
+impl Encoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Identity => "identity",
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+            Self::Brotli => "br",
+        }
+    }
+}
+
+/// Picks the offered coding the `Accept-Encoding` header prefers, in offered order on ties.
+///
+/// `*` stands for the codings the header does not list. Falls back to identity when no
+/// offered coding is acceptable, as well as when the header is absent.
+pub(crate) fn negotiate_encoding(accept: Option<&str>, offered: &[Encoding]) -> Encoding {
+    let codings = match accept {
+        Some(accept) => parse_quality_list(accept),
+        None => return Encoding::Identity,
+    };
+
+    let quality = |e: &Encoding| {
+        codings
+            .iter()
+            .find(|(c, _)| c.eq_ignore_ascii_case(e.as_str()))
+            .or_else(|| codings.iter().find(|(c, _)| c == "*"))
+            .map(|(_, q)| *q)
+    };
+
+    offered
+        .iter()
+        .filter(|e| **e != Encoding::Identity)
+        .filter_map(|e| quality(e).map(|q| (*e, q)))
+        .filter(|(_, q)| *q > 0.0)
+        .fold(None, |best: Option<(Encoding, f32)>, (e, q)| match best {
+            Some((_, b)) if b >= q => best,
+            _ => Some((e, q)),
+        })
+        .map_or(Encoding::Identity, |(e, _)| e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{negotiate, negotiate_encoding, parse_quality_list, Encoding};
+
+    #[test]
+    fn quality_list() {
+        assert_eq!(
+            parse_quality_list("text/html, application/json;q=0.9, */*;q=0.1"),
+            vec![
+                ("text/html".to_owned(), 1.0),
+                ("application/json".to_owned(), 0.9),
+                ("*/*".to_owned(), 0.1),
+            ]
+        );
+
+        assert_eq!(
+            parse_quality_list("text/html;level=1;Q=0.5 , ,gzip;q=abc, br;q=2, fr;q=-1"),
+            vec![
+                ("text/html;level=1".to_owned(), 0.5),
+                ("gzip".to_owned(), 1.0),
+                ("br".to_owned(), 1.0),
+                ("fr".to_owned(), 0.0),
+            ]
+        );
+
+        assert!(parse_quality_list("").is_empty());
+    }
+
+    #[test]
+    fn negotiation() {
+        let offered = [mime::APPLICATION_JSON, mime::TEXT_PLAIN];
+
+        assert_eq!(negotiate(None, &offered), Some(mime::APPLICATION_JSON));
+        assert_eq!(
+            negotiate(Some("*/*"), &offered),
+            Some(mime::APPLICATION_JSON)
+        );
+        assert_eq!(
+            negotiate(Some("application/json;q=0.5, text/*"), &offered),
+            Some(mime::TEXT_PLAIN)
+        );
+        assert_eq!(
+            negotiate(Some("text/*;q=0.2, text/plain, */*;q=0.5"), &offered),
+            Some(mime::TEXT_PLAIN)
+        );
+        assert_eq!(negotiate(Some("text/plain;q=0, image/*"), &offered), None);
+    }
+
+    #[test]
+    fn encoding_negotiation() {
+        let offered = [Encoding::Brotli, Encoding::Gzip];
+
+        assert_eq!(negotiate_encoding(None, &offered), Encoding::Identity);
+        assert_eq!(
+            negotiate_encoding(Some("gzip, br"), &offered),
+            Encoding::Brotli
+        );
+        assert_eq!(
+            negotiate_encoding(Some("gzip, br;q=0.5"), &offered),
+            Encoding::Gzip
+        );
+        assert_eq!(
+            negotiate_encoding(Some("deflate, *;q=0.1"), &offered),
+            Encoding::Brotli
+        );
+        assert_eq!(
+            negotiate_encoding(Some("*, br;q=0"), &offered),
+            Encoding::Gzip
+        );
+        assert_eq!(
+            negotiate_encoding(Some("deflate"), &offered),
+            Encoding::Identity
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use crate::structured::{self, BareItem, Member};
+
+/// The scheduling hints of the `Priority` header (RFC 9218)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Priority {
+    /// `u`, from `0` (highest) to `7` (lowest), `3` by default
+    pub urgency: u8,
+    /// `i`, whether the response can be processed incrementally, `false` by default
+    pub incremental: bool,
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Self {
+            urgency: 3,
+            incremental: false,
+        }
+    }
+}
+
+impl Priority {
+    /// Parses the dictionary, ignoring unknown members and out-of-range values
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        let mut priority = Self::default();
+        let bare = |m: &Member| m.as_item().map(|i| i.bare.clone());
+
+        for (key, member) in structured::parse_dictionary(s)? {
+            match (key.as_str(), bare(&member)) {
+                ("u", Some(BareItem::Integer(u @ 0..=7))) => priority.urgency = u as u8,
+                ("i", Some(BareItem::Boolean(i))) => priority.incremental = i,
+                _ => {}
+            }
+        }
+
+        Some(priority)
+    }
+}
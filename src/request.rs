@@ -1,6 +1,71 @@
-use crate::{anyhow::Result, async_trait, header, Body, Error, Request};
+use crate::{
+    anyhow::Result,
+    async_trait,
+    etag::{Condition, ETag},
+    header,
+    structured::StructuredValue,
+    Body, Encoding, Error, FetchDest, FetchMetadata, FetchMode, FetchSite, IntoResponse, Method,
+    Priority, Request, Response, ResponseExt,
+};
 use futures_util::stream::{Stream, StreamExt};
 
+#[cfg(feature = "decompress")]
+use futures_util::stream::{self, BoxStream};
+
+/// The request body as read before the handler ran
+#[derive(Clone, Debug)]
+pub(crate) struct RawBody(pub(crate) bytes::Bytes);
+
+/// The size limit of the bodies [`RequestExt::text`], [`RequestExt::json`] and
+/// [`RequestExt::form`] read, 8 MiB unless overridden by inserting one into the request
+/// extensions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BodyLimit(pub usize);
+
+impl Default for BodyLimit {
+    fn default() -> Self {
+        Self(8 * 1024 * 1024)
+    }
+}
+
+/// The limits of [`RequestExt::collect`]
+#[cfg(feature = "time")]
+#[derive(Clone, Copy, Debug)]
+pub struct BodyConfig {
+    limit: usize,
+    timeout: Option<std::time::Duration>,
+}
+
+#[cfg(feature = "time")]
+impl Default for BodyConfig {
+    /// Limits the body to 8 MiB, without a deadline
+    fn default() -> Self {
+        Self {
+            limit: BodyLimit::default().0,
+            timeout: None,
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+impl BodyConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The maximum size of the body in bytes
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// The deadline to read the whole body within
+    pub fn timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.timeout.replace(timeout);
+        self
+    }
+}
+
 #[async_trait]
 pub trait RequestExt {
     fn query_string(&self) -> &str;
@@ -9,19 +74,217 @@ pub trait RequestExt {
 
     fn content_type(&self) -> Option<mime::Mime>;
 
+    fn http_version(&self) -> crate::Version;
+
+    /// Parses the first value of the header, ignoring any further ones, see
+    /// [`headers_all`](Self::headers_all)
     fn header<T>(&self, key: impl AsRef<str>) -> Option<T>
     where
         T: std::str::FromStr;
 
+    /// Parses every value of the header, skipping those which fail to parse.
+    ///
+    /// Each header line is one value; split comma-joined lists with
+    /// [`header_list`](Self::header_list) instead, as values like dates may contain commas.
+    fn headers_all<T>(&self, key: impl AsRef<str>) -> Vec<T>
+    where
+        T: std::str::FromStr;
+
+    /// The elements of a list header, whether sent on several lines or comma-joined on one,
+    /// trimmed and without empty ones. Commas inside quoted strings do not split elements.
+    fn header_list(&self, key: impl AsRef<str>) -> Vec<String>;
+
+    /// Whether the request arrived over HTTPS, consulting the first `X-Forwarded-Proto`
+    /// value, or else the first `Forwarded` element's `proto`, only when `trust_forwarded`.
+    ///
+    /// Only trust the forwarded headers when every request passes through a proxy that
+    /// overwrites them.
+    fn is_secure(&self, trust_forwarded: bool) -> bool;
+
+    /// The lowercased host and the port the request was sent to, from the authority of an
+    /// absolute URI, as HTTP/2's `:authority` is, or else the single `Host` header.
+    ///
+    /// IPv6 literals keep their brackets, e.g. `[::1]`. Returns `None` when there are
+    /// several `Host` headers or the value is not a bare host and port, e.g. carries
+    /// userinfo, a path or control characters.
+    fn host(&self) -> Option<(String, Option<u16>)>;
+
+    /// The `(first, last, complete length)` of a `Content-Range: bytes first-last/length`
+    /// upload chunk.
+    ///
+    /// Returns `None` when the header is malformed, the range does not fit the complete
+    /// length, or the `Content-Length` disagrees with the size of the range.
+    fn content_range(&self) -> Option<(u64, u64, u64)>;
+
+    /// The inclusive byte ranges the `Range` header requests of a representation of
+    /// `total_len` bytes, sorted with overlapping and adjacent ranges merged.
+    ///
+    /// Suffix ranges, `-500`, count from the end, and ends past the representation are
+    /// clamped to it. Returns `None` when there is no `Range` header, or it is malformed or
+    /// not in bytes, since such headers are ignored. Fails with
+    /// [`Error::RangeNotSatisfiable`], to respond `416`, when no range overlaps the
+    /// representation.
+    fn ranges(&self, total_len: u64) -> Option<Result<Vec<(u64, u64)>>>;
+
+    /// Whether the client sent `Expect: 100-continue` and waits for the go-ahead before
+    /// sending the body
+    fn expects_continue(&self) -> bool;
+
+    /// The remaining hops of a `TRACE` or `OPTIONS` request, `None` when the `Max-Forwards`
+    /// header is missing or not a decimal number
+    fn max_forwards(&self) -> Option<u32>;
+
+    /// The scheme and raw credentials of the `Authorization` header, for schemes beyond
+    /// [`bearer_token`](Self::bearer_token) and [`basic_auth`](Self::basic_auth), e.g.
+    /// `("HMAC-SHA256", "keyId=..., signature=...")`. `None` when the header is missing or
+    /// has no credentials.
+    fn authorization(&self) -> Option<(String, String)>;
+
+    /// The token of an `Authorization: Bearer <token>` header, `None` when the header is
+    /// missing, of another scheme or has an empty token
+    fn bearer_token(&self) -> Option<String>;
+
+    /// The user-id and password of an `Authorization: Basic <credentials>` header.
+    ///
+    /// The password is everything after the first `:`, so it may contain colons. `None`
+    /// when the header is missing, of another scheme, or not base64 encoded UTF-8.
+    fn basic_auth(&self) -> Option<(String, String)>;
+
+    /// The media ranges of the `Accept` header, most preferred first, keeping the header's
+    /// order on ties.
+    ///
+    /// Malformed ranges are skipped, and malformed q-values count as `1.0`. A missing or
+    /// empty header accepts everything, as `*/*`.
+    fn accepts(&self) -> Vec<(mime::Mime, f32)>;
+
+    /// Picks the offered media type the `Accept` header prefers, the first one when the
+    /// header is missing or empty
+    fn preferred(&self, offered: &[mime::Mime]) -> Option<mime::Mime>;
+
+    /// Picks the offered content coding the `Accept-Encoding` header prefers, identity
+    /// when none is acceptable.
+    ///
+    /// Requests with a `Range` header always get identity, since ranges apply to the
+    /// identity representation and a compressed body would not match the requested bytes.
+    /// Responses should still carry `Vary: Accept-Encoding`.
+    fn preferred_encoding(&self, offered: &[Encoding]) -> Encoding;
+
+    /// Parses a structured field header (RFC 8941), see [`StructuredValue::parse`]
+    fn structured_header(&self, key: impl AsRef<str>) -> Option<StructuredValue>;
+
+    /// Parses the `Priority` header, `None` when it is missing or malformed. Members it
+    /// omits take the defaults of [`Priority::default`].
+    fn priority(&self) -> Option<Priority>;
+
+    /// Parses the `Sec-Fetch-*` metadata headers, leaving unknown values out
+    fn fetch_metadata(&self) -> FetchMetadata;
+
+    /// Whether the request was sent by a script rather than a navigation, per
+    /// `X-Requested-With: XMLHttpRequest` or a `Sec-Fetch-Mode` of `cors` or `same-origin`
+    fn is_ajax(&self) -> bool;
+
+    /// Evaluates `If-Match` and `If-None-Match` against the current entity tag of the
+    /// resource, `None` when it does not exist, per RFC 7232.
+    ///
+    /// `If-Match` uses the strong comparison and fails with `412`, also when malformed.
+    /// `If-None-Match` uses the weak comparison and fails with `304` for `GET` and `HEAD`,
+    /// otherwise `412`. `*` matches any existing resource. Returns `None` when the request
+    /// should proceed.
+    fn preconditions(&self, etag: Option<&str>) -> Option<Response<Body>>;
+
+    /// The raw body captured by [`Router::capture_body`](crate::Router::capture_body),
+    /// still available after the body itself has been consumed
+    fn raw_body(&self) -> Option<&bytes::Bytes>;
+
+    /// Reads the whole body within the size limit and deadline of the config.
+    ///
+    /// Fails with [`Error::PayloadTooLarge`] as soon as the body, or its declared
+    /// `Content-Length`, exceeds the limit, and with [`Error::Timeout`] when the deadline
+    /// passes first.
+    #[cfg(feature = "time")]
+    async fn collect(self, config: BodyConfig) -> Result<bytes::Bytes>;
+
     async fn bytes<T>(stream: T) -> Result<bytes::Bytes>
     where
-        T: Send + Unpin + Stream<Item = Result<bytes::Bytes, Error>>;
+        T: Send + Unpin + Stream<Item = Result<bytes::Bytes, hyper::Error>>;
+
+    /// Reads the stream like [`bytes`](Self::bytes), failing with [`Error::PayloadTooLarge`]
+    /// as soon as it exceeds `limit` bytes
+    async fn bytes_limited<T>(stream: T, limit: usize) -> Result<bytes::Bytes>
+    where
+        T: Send + Unpin + Stream<Item = Result<bytes::Bytes, hyper::Error>>;
+
+    /// Forwards the body chunk by chunk into the channel, for a consumer task to process
+    /// the upload as it arrives. Reading waits while the channel is full.
+    ///
+    /// Stops reading and fails as soon as the receiver is dropped.
+    #[cfg(feature = "sync")]
+    async fn pipe_to(self, tx: tokio::sync::mpsc::Sender<bytes::Bytes>) -> Result<()>;
+
+    /// Streams the body, inflating it incrementally per its `Content-Encoding`, one of
+    /// `gzip`, `deflate`, `br` and `identity`, or a list of them undone last first, in
+    /// chunks of at most 8 KiB.
+    ///
+    /// Fails with [`Error::UnsupportedMediaType`] for any other coding.
+    #[cfg(feature = "decompress")]
+    fn decoded_stream(self) -> Result<BoxStream<'static, Result<bytes::Bytes>>>;
+
+    /// Reads the body within the [`BodyLimit`], as sent, and verifies it against the
+    /// `sha-256` or `sha-512` digests of the `Content-Digest` header, or else of the
+    /// `Repr-Digest` header (RFC 9530).
+    ///
+    /// Fails with [`Error::DigestMismatch`] when a digest does not match, or neither header
+    /// has a supported digest.
+    #[cfg(feature = "digest")]
+    async fn verify_digest(self) -> Result<bytes::Bytes>;
+
+    /// Checks the CSRF token submitted in the `X-CSRF-Token` header, or else the
+    /// `csrf_token` field of a url-encoded form, against the signed cookie set by
+    /// [`ResponseExt::set_csrf_token`](crate::ResponseExt::set_csrf_token).
+    ///
+    /// The form is read within the [`BodyLimit`] and put back, so handlers can still read
+    /// it. `false` when either token is missing, the cookie is not signed with `key`, or the
+    /// tokens differ.
+    #[cfg(feature = "csrf")]
+    async fn verify_csrf(&mut self, key: &[u8]) -> bool;
+
+    /// Verifies the HS256 JWT of the `Authorization: Bearer` header with the key, including
+    /// its `exp` and `nbf` claims, and deserializes its claims.
+    ///
+    /// Fails with [`Error::Unauthorized`] when the token is missing, invalid or expired.
+    #[cfg(feature = "jwt")]
+    fn jwt_claims<T>(&self, key: &[u8]) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned;
+
+    /// Reads the body within the [`BodyLimit`] and decodes it per the `charset` of the
+    /// `Content-Type`, UTF-8 when missing; `us-ascii` and `iso-8859-1` are also understood.
+    ///
+    /// Fails when the body is not valid in the charset, and with
+    /// [`Error::UnsupportedMediaType`] for other charsets.
+    async fn text(self) -> Result<String>;
+
+    /// Reads the body like [`text`](Self::text), replacing invalid sequences with `U+FFFD`
+    async fn text_lossy(self) -> Result<String>;
 
     #[cfg(feature = "json")]
     async fn json<T>(self) -> Result<T>
     where
         T: serde::de::DeserializeOwned;
 
+    /// Deserializes a MessagePack body, of `application/msgpack` or
+    /// `application/x-msgpack`
+    #[cfg(feature = "msgpack")]
+    async fn msgpack<T>(self) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned;
+
+    /// Deserializes a CBOR body, of `application/cbor`
+    #[cfg(feature = "cbor")]
+    async fn cbor<T>(self) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned;
+
     #[cfg(feature = "form")]
     async fn form<T>(self) -> Result<T>
     where
@@ -32,6 +295,24 @@ pub trait RequestExt {
     where
         T: serde::de::DeserializeOwned;
 
+    /// Deserializes the query like [`query`](Self::query), but tolerant of the bool formats
+    /// APIs commonly send: `true`/`false`, `yes`/`no`, `on`/`off` and `1`/`0`, in any case.
+    ///
+    /// Numbers are parsed from the values as usual.
+    #[cfg(feature = "query")]
+    fn query_lenient<T>(&self) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned;
+
+    /// Deserializes the path parameters captured by the matched route.
+    ///
+    /// Values are parsed into the field types, and `{name:int}` parameters deserialize as
+    /// integers even into self-describing types.
+    #[cfg(feature = "serde")]
+    fn params<T>(&self) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned;
+
     #[cfg(feature = "multipart")]
     fn multipart(self) -> Result<form_data::FormData<Body>>;
 
@@ -41,17 +322,40 @@ pub trait RequestExt {
     #[cfg(feature = "cookie")]
     fn cookie(&mut self, name: impl AsRef<str>) -> Option<cookie::Cookie<'static>>;
 
+    /// Parses the `Cookie` header into a fresh jar on every call, for handlers holding only
+    /// `&Request`. Unlike [`cookie_jar`](Self::cookie_jar) nothing is cached, and malformed
+    /// pairs are skipped.
+    #[cfg(feature = "cookie")]
+    fn parse_cookies(&self) -> cookie::CookieJar;
+
+    /// The cookie of the name with its signature verified and stripped, `None` when it is
+    /// absent or was tampered with, see
+    /// [`ResponseExt::set_signed_cookie`](crate::ResponseExt::set_signed_cookie)
+    #[cfg(feature = "cookie-secure")]
+    fn signed_cookie(
+        &mut self,
+        key: &cookie::Key,
+        name: impl AsRef<str>,
+    ) -> Option<cookie::Cookie<'static>>;
+
+    /// The cookie of the name authenticated and decrypted, `None` when it is absent or was
+    /// tampered with, see
+    /// [`ResponseExt::set_private_cookie`](crate::ResponseExt::set_private_cookie)
+    #[cfg(feature = "cookie-secure")]
+    fn private_cookie(
+        &mut self,
+        key: &cookie::Key,
+        name: impl AsRef<str>,
+    ) -> Option<cookie::Cookie<'static>>;
+
     #[cfg(feature = "ws")]
     fn ws() -> Result<()>;
-
-    #[cfg(feature = "sse")]
-    fn sse() -> Result<()>;
 }
 
 #[async_trait]
 impl RequestExt for Request<Body> {
     fn query_string(&self) -> &str {
-        self.uri().query().unwrap_or_default().as_ref()
+        self.uri().query().unwrap_or_default()
     }
 
     fn content_length(&self) -> Option<u64> {
@@ -62,6 +366,10 @@ impl RequestExt for Request<Body> {
         self.header(header::CONTENT_TYPE)
     }
 
+    fn http_version(&self) -> crate::Version {
+        self.version()
+    }
+
     fn header<T>(&self, key: impl AsRef<str>) -> Option<T>
     where
         T: std::str::FromStr,
@@ -72,9 +380,354 @@ impl RequestExt for Request<Body> {
             .and_then(|v| v.parse::<T>().ok())
     }
 
+    fn headers_all<T>(&self, key: impl AsRef<str>) -> Vec<T>
+    where
+        T: std::str::FromStr,
+    {
+        self.headers()
+            .get_all(key.as_ref())
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .filter_map(|v| v.trim().parse::<T>().ok())
+            .collect()
+    }
+
+    fn header_list(&self, key: impl AsRef<str>) -> Vec<String> {
+        let mut list = Vec::new();
+        for value in self.headers().get_all(key.as_ref()) {
+            let value = match value.to_str() {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+
+            let (mut start, mut quoted, mut escaped) = (0, false, false);
+            for (i, c) in value.char_indices() {
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' if quoted => escaped = true,
+                    '"' => quoted = !quoted,
+                    ',' if !quoted => {
+                        list.push(value[start..i].trim().to_owned());
+                        start = i + 1;
+                    }
+                    _ => {}
+                }
+            }
+            list.push(value[start..].trim().to_owned());
+        }
+        list.retain(|element| !element.is_empty());
+        list
+    }
+
+    fn is_secure(&self, trust_forwarded: bool) -> bool {
+        if self.uri().scheme() == Some(&crate::http::uri::Scheme::HTTPS) {
+            return true;
+        }
+
+        if !trust_forwarded {
+            return false;
+        }
+
+        if let Some(proto) = self.header::<String>("x-forwarded-proto") {
+            return proto
+                .split(',')
+                .next()
+                .is_some_and(|p| p.trim().eq_ignore_ascii_case("https"));
+        }
+
+        self.header::<String>(header::FORWARDED).is_some_and(|v| {
+            v.split(',')
+                .next()
+                .unwrap_or_default()
+                .split(';')
+                .filter_map(|pair| pair.split_once('='))
+                .any(|(k, v)| {
+                    k.trim().eq_ignore_ascii_case("proto")
+                        && v.trim().trim_matches('"').eq_ignore_ascii_case("https")
+                })
+        })
+    }
+
+    fn host(&self) -> Option<(String, Option<u16>)> {
+        let authority = match self.uri().authority() {
+            Some(authority) => authority.as_str(),
+            None => {
+                let mut hosts = self.headers().get_all(header::HOST).iter();
+                match (hosts.next(), hosts.next()) {
+                    (Some(host), None) => host.to_str().ok()?,
+                    _ => return None,
+                }
+            }
+        };
+
+        let (host, port) = match authority.rfind(':') {
+            Some(i) if !authority[i..].contains(']') => {
+                let port = &authority[i + 1..];
+                let port = match port {
+                    "" => None,
+                    _ if port.bytes().all(|b| b.is_ascii_digit()) => Some(port.parse().ok()?),
+                    _ => return None,
+                };
+                (&authority[..i], port)
+            }
+            _ => (authority, None),
+        };
+
+        let valid = match host.strip_prefix('[').and_then(|h| h.strip_suffix(']')) {
+            Some(ip) => ip.parse::<std::net::Ipv6Addr>().is_ok(),
+            None => {
+                !host.is_empty()
+                    && host.bytes().all(|b| {
+                        b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~')
+                    })
+            }
+        };
+
+        valid.then(|| (host.to_ascii_lowercase(), port))
+    }
+
+    fn content_range(&self) -> Option<(u64, u64, u64)> {
+        let value = self.header::<String>(header::CONTENT_RANGE)?;
+        let (unit, range) = value.trim().split_once(' ')?;
+        if !unit.eq_ignore_ascii_case("bytes") {
+            return None;
+        }
+
+        let (range, length) = range.trim().split_once('/')?;
+        let (first, last) = range.split_once('-')?;
+        let parse = |s: &str| {
+            Some(s)
+                .filter(|s| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()))?
+                .parse::<u64>()
+                .ok()
+        };
+        let (first, last, length) = (parse(first)?, parse(last)?, parse(length)?);
+
+        if first > last || last >= length {
+            return None;
+        }
+        if self.content_length().is_some_and(|n| n != last - first + 1) {
+            return None;
+        }
+
+        Some((first, last, length))
+    }
+
+    fn ranges(&self, total_len: u64) -> Option<Result<Vec<(u64, u64)>>> {
+        let value = self.header::<String>(header::RANGE)?;
+        let (unit, specs) = value.trim().split_once('=')?;
+        if !unit.trim().eq_ignore_ascii_case("bytes") {
+            return None;
+        }
+
+        let parse = |s: &str| {
+            Some(s)
+                .filter(|s| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()))?
+                .parse::<u64>()
+                .ok()
+        };
+
+        let mut ranges = Vec::new();
+        for spec in specs.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (first, last) = spec.split_once('-')?;
+            let (first, last) = match (first.trim(), last.trim()) {
+                ("", suffix) => (total_len.saturating_sub(parse(suffix)?), total_len),
+                (first, "") => (parse(first)?, u64::MAX),
+                (first, last) => (parse(first)?, parse(last)?),
+            };
+            if first > last {
+                return None;
+            }
+            // unsatisfiable ones, including `-0`, are left out
+            if first < total_len {
+                ranges.push((first, last.min(total_len - 1)));
+            }
+        }
+
+        if ranges.is_empty() {
+            return Some(Err(Error::RangeNotSatisfiable.into()));
+        }
+
+        ranges.sort_unstable();
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+        for (first, last) in ranges {
+            match merged.last_mut() {
+                Some((_, end)) if first <= end.saturating_add(1) => *end = (*end).max(last),
+                _ => merged.push((first, last)),
+            }
+        }
+        Some(Ok(merged))
+    }
+
+    fn expects_continue(&self) -> bool {
+        self.header::<String>(header::EXPECT)
+            .is_some_and(|v| v.trim().eq_ignore_ascii_case("100-continue"))
+    }
+
+    fn max_forwards(&self) -> Option<u32> {
+        self.header::<String>(header::MAX_FORWARDS)
+            .filter(|v| !v.is_empty() && v.bytes().all(|b| b.is_ascii_digit()))?
+            .parse()
+            .ok()
+    }
+
+    fn authorization(&self) -> Option<(String, String)> {
+        let value = self.header::<String>(header::AUTHORIZATION)?;
+        let (scheme, credentials) = value.trim().split_once([' ', '\t'])?;
+        let credentials = credentials.trim();
+        Some((scheme.to_owned(), credentials.to_owned())).filter(|_| !credentials.is_empty())
+    }
+
+    fn bearer_token(&self) -> Option<String> {
+        let (scheme, token) = self.authorization()?;
+        Some(token).filter(|_| scheme.eq_ignore_ascii_case("bearer"))
+    }
+
+    fn basic_auth(&self) -> Option<(String, String)> {
+        let (scheme, credentials) = self.authorization()?;
+        if !scheme.eq_ignore_ascii_case("basic") {
+            return None;
+        }
+        let decoded = crate::structured::decode_base64(credentials.as_bytes())?;
+        let credentials = String::from_utf8(decoded).ok()?;
+        let (user, password) = credentials.split_once(':')?;
+        Some((user.to_owned(), password.to_owned()))
+    }
+
+    fn accepts(&self) -> Vec<(mime::Mime, f32)> {
+        let accept = self.header_list(header::ACCEPT).join(", ");
+        if accept.is_empty() {
+            return vec![(mime::STAR_STAR, 1.0)];
+        }
+
+        let mut ranges = crate::negotiation::parse_quality_list(&accept)
+            .into_iter()
+            .filter_map(|(range, q)| Some((range.parse().ok()?, q)))
+            .collect::<Vec<_>>();
+        ranges.sort_by(|(_, a): &(mime::Mime, f32), (_, b)| b.total_cmp(a));
+        ranges
+    }
+
+    fn preferred(&self, offered: &[mime::Mime]) -> Option<mime::Mime> {
+        let accept = self.header_list(header::ACCEPT).join(", ");
+        crate::negotiation::negotiate(Some(accept.as_str()).filter(|a| !a.is_empty()), offered)
+    }
+
+    fn preferred_encoding(&self, offered: &[Encoding]) -> Encoding {
+        if self.headers().contains_key(header::RANGE) {
+            return Encoding::Identity;
+        }
+
+        let accept = self
+            .headers()
+            .get(header::ACCEPT_ENCODING)
+            .and_then(|v| v.to_str().ok());
+        crate::negotiation::negotiate_encoding(accept, offered)
+    }
+
+    fn structured_header(&self, key: impl AsRef<str>) -> Option<StructuredValue> {
+        let values = self
+            .headers()
+            .get_all(key.as_ref())
+            .iter()
+            .map(|v| v.to_str().ok())
+            .collect::<Option<Vec<_>>>()?;
+        if values.is_empty() {
+            return None;
+        }
+        StructuredValue::parse(&values.join(", "))
+    }
+
+    fn priority(&self) -> Option<Priority> {
+        self.header::<String>("priority")
+            .and_then(|v| Priority::parse(&v))
+    }
+
+    fn fetch_metadata(&self) -> FetchMetadata {
+        let value = |name| self.header::<String>(name);
+
+        FetchMetadata {
+            site: value("sec-fetch-site").and_then(|v| FetchSite::parse(v.trim())),
+            mode: value("sec-fetch-mode").and_then(|v| FetchMode::parse(v.trim())),
+            dest: value("sec-fetch-dest").map(|v| FetchDest::parse(v.trim())),
+            user: value("sec-fetch-user").is_some_and(|v| v.trim() == "?1"),
+        }
+    }
+
+    fn is_ajax(&self) -> bool {
+        self.header::<String>("x-requested-with")
+            .is_some_and(|v| v.trim().eq_ignore_ascii_case("xmlhttprequest"))
+            || matches!(
+                self.fetch_metadata().mode,
+                Some(FetchMode::Cors) | Some(FetchMode::SameOrigin)
+            )
+    }
+
+    fn preconditions(&self, etag: Option<&str>) -> Option<Response<Body>> {
+        let current = etag.and_then(ETag::parse);
+        let condition = |name| {
+            self.headers()
+                .get(name)
+                .map(|v| v.to_str().ok().and_then(Condition::parse))
+        };
+
+        if let Some(condition) = condition(header::IF_MATCH) {
+            if !condition.is_some_and(|c| c.matches(current.as_ref(), ETag::strong_eq)) {
+                return Some(Response::precondition_failed());
+            }
+        }
+
+        if let Some(Some(condition)) = condition(header::IF_NONE_MATCH) {
+            if condition.matches(current.as_ref(), ETag::weak_eq) {
+                return Some(match (self.method(), etag) {
+                    (&Method::GET, Some(etag)) | (&Method::HEAD, Some(etag)) => {
+                        Response::not_modified(etag).into_response()
+                    }
+                    _ => Response::precondition_failed(),
+                });
+            }
+        }
+
+        None
+    }
+
+    fn raw_body(&self) -> Option<&bytes::Bytes> {
+        self.extensions().get::<RawBody>().map(|b| &b.0)
+    }
+
+    #[cfg(feature = "time")]
+    async fn collect(self, config: BodyConfig) -> Result<bytes::Bytes> {
+        if self
+            .content_length()
+            .is_some_and(|n| n > config.limit as u64)
+        {
+            return Err(Error::PayloadTooLarge.into());
+        }
+
+        let mut stream = self.into_body();
+        let read = async move {
+            let mut body = bytes::BytesMut::new();
+            while let Some(chunk) = stream.next().await {
+                let chunk = chunk?;
+                if body.len() + chunk.len() > config.limit {
+                    return Err(Error::PayloadTooLarge.into());
+                }
+                body.extend_from_slice(&chunk);
+            }
+            Ok(body.freeze())
+        };
+
+        match config.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, read)
+                .await
+                .map_err(|_| Error::Timeout)?,
+            None => read.await,
+        }
+    }
+
     async fn bytes<T>(mut stream: T) -> Result<bytes::Bytes>
     where
-        T: Send + Unpin + Stream<Item = Result<bytes::Bytes, Error>>,
+        T: Send + Unpin + Stream<Item = Result<bytes::Bytes, hyper::Error>>,
     {
         let mut body = bytes::BytesMut::with_capacity(8192);
 
@@ -85,6 +738,127 @@ impl RequestExt for Request<Body> {
         Ok(body.freeze())
     }
 
+    #[cfg(feature = "sync")]
+    async fn pipe_to(self, tx: tokio::sync::mpsc::Sender<bytes::Bytes>) -> Result<()> {
+        let mut body = self.into_body();
+        while let Some(chunk) = body.next().await {
+            tx.send(chunk?).await?;
+        }
+        Ok(())
+    }
+
+    async fn bytes_limited<T>(mut stream: T, limit: usize) -> Result<bytes::Bytes>
+    where
+        T: Send + Unpin + Stream<Item = Result<bytes::Bytes, hyper::Error>>,
+    {
+        let mut body = bytes::BytesMut::with_capacity(limit.min(8192));
+
+        while let Some(item) = stream.next().await {
+            let chunk = item?;
+            if body.len() + chunk.len() > limit {
+                return Err(Error::PayloadTooLarge.into());
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        Ok(body.freeze())
+    }
+
+    #[cfg(feature = "decompress")]
+    fn decoded_stream(self) -> Result<BoxStream<'static, Result<bytes::Bytes>>> {
+        decode_body(self, usize::MAX)
+    }
+
+    #[cfg(feature = "digest")]
+    async fn verify_digest(self) -> Result<bytes::Bytes> {
+        let digest = self
+            .header::<String>("content-digest")
+            .or_else(|| self.header("repr-digest"))
+            .ok_or(Error::DigestMismatch)?;
+
+        let BodyLimit(limit) = self.extensions().get().copied().unwrap_or_default();
+        let body = Self::bytes_limited(self.into_body(), limit).await?;
+
+        match crate::digest::verify(&digest, &body) {
+            Some(true) => Ok(body),
+            Some(false) | None => Err(Error::DigestMismatch.into()),
+        }
+    }
+
+    #[cfg(feature = "csrf")]
+    async fn verify_csrf(&mut self, key: &[u8]) -> bool {
+        let jar = self.parse_cookies();
+        let token = match jar
+            .get(crate::csrf::COOKIE)
+            .and_then(|c| crate::csrf::verify(key, c.value()))
+        {
+            Some(token) => token,
+            None => return false,
+        };
+
+        if let Some(submitted) = self.header::<String>(crate::csrf::HEADER) {
+            return crate::csrf::eq(submitted.trim(), token);
+        }
+
+        let is_form = self
+            .content_type()
+            .is_some_and(|m| m.essence_str() == mime::APPLICATION_WWW_FORM_URLENCODED);
+        if !is_form {
+            return false;
+        }
+
+        let BodyLimit(limit) = self.extensions().get().copied().unwrap_or_default();
+        let body = std::mem::take(self.body_mut());
+        let body = match Request::bytes_limited(body, limit).await {
+            Ok(body) => body,
+            Err(_) => return false,
+        };
+        *self.body_mut() = Body::from(body.clone());
+
+        serde_urlencoded::from_bytes::<Vec<(String, String)>>(&body)
+            .ok()
+            .and_then(|fields| fields.into_iter().find(|(k, _)| k == crate::csrf::FIELD))
+            .is_some_and(|(_, submitted)| crate::csrf::eq(&submitted, token))
+    }
+
+    #[cfg(feature = "jwt")]
+    fn jwt_claims<T>(&self, key: &[u8]) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let token = self.bearer_token().ok_or(Error::Unauthorized)?;
+
+        let payload = crate::jwt::verify(&token, key).map_err(|e| match e.downcast::<Error>() {
+            Ok(e) => e.into(),
+            Err(_) => anyhow::Error::new(Error::Unauthorized),
+        })?;
+
+        serde_json::from_slice(&payload).map_err(Into::into)
+    }
+
+    async fn text(self) -> Result<String> {
+        let charset = Charset::of(&self)?;
+        let body = limited_body(self).await?;
+        match charset {
+            Charset::Utf8 => String::from_utf8(body.to_vec()).map_err(Into::into),
+            Charset::Ascii if !body.is_ascii() => anyhow::bail!("body is not valid US-ASCII"),
+            Charset::Ascii | Charset::Latin1 => Ok(body.iter().map(|b| *b as char).collect()),
+        }
+    }
+
+    async fn text_lossy(self) -> Result<String> {
+        let charset = Charset::of(&self)?;
+        let body = limited_body(self).await?;
+        Ok(match charset {
+            Charset::Utf8 => String::from_utf8_lossy(&body).into_owned(),
+            Charset::Ascii => body
+                .iter()
+                .map(|b| if b.is_ascii() { *b as char } else { '\u{fffd}' })
+                .collect(),
+            Charset::Latin1 => body.iter().map(|b| *b as char).collect(),
+        })
+    }
+
     #[cfg(feature = "json")]
     async fn json<T>(self) -> Result<T>
     where
@@ -98,9 +872,45 @@ impl RequestExt for Request<Body> {
             })
             .is_some();
 
-        anyhow::ensure!(valid, "Content-Type is not JSON");
+        anyhow::ensure!(valid, Error::UnsupportedMediaType);
+
+        let body = limited_body(self).await?;
+        serde_json::from_slice(&body).map_err(Into::into)
+    }
+
+    #[cfg(feature = "msgpack")]
+    async fn msgpack<T>(self) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let valid = self
+            .content_type()
+            .filter(|m| {
+                m.type_() == mime::APPLICATION
+                    && matches!(m.subtype().as_str(), "msgpack" | "x-msgpack")
+            })
+            .is_some();
+
+        anyhow::ensure!(valid, Error::UnsupportedMediaType);
+
+        let body = limited_body(self).await?;
+        rmp_serde::from_slice(&body).map_err(Into::into)
+    }
+
+    #[cfg(feature = "cbor")]
+    async fn cbor<T>(self) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let valid = self
+            .content_type()
+            .filter(|m| m.type_() == mime::APPLICATION && m.subtype() == "cbor")
+            .is_some();
+
+        anyhow::ensure!(valid, Error::UnsupportedMediaType);
 
-        serde_json::from_slice(&Self::bytes(self.into_body()).await?).map_err(Into::into)
+        let body = limited_body(self).await?;
+        ciborium::de::from_reader(bytes::Buf::reader(body)).map_err(Into::into)
     }
 
     #[cfg(feature = "form")]
@@ -113,9 +923,9 @@ impl RequestExt for Request<Body> {
             .filter(|m| m.type_() == mime::APPLICATION && m.subtype() == mime::WWW_FORM_URLENCODED)
             .is_some();
 
-        anyhow::ensure!(valid, "Content-Type is not Form");
+        anyhow::ensure!(valid, Error::UnsupportedMediaType);
 
-        serde_urlencoded::from_reader(bytes::Buf::reader(Self::bytes(self.into_body()).await?))
+        serde_urlencoded::from_reader(bytes::Buf::reader(limited_body(self).await?))
             .map_err(Into::into)
     }
 
@@ -127,12 +937,48 @@ impl RequestExt for Request<Body> {
         serde_urlencoded::from_str(self.query_string()).map_err(Into::into)
     }
 
+    #[cfg(feature = "query")]
+    fn query_lenient<T>(&self) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let pairs = serde_urlencoded::from_str::<Vec<(String, String)>>(self.query_string())?;
+
+        T::deserialize(serde::de::value::MapDeserializer::new(
+            pairs
+                .iter()
+                .map(|(k, v)| (k.as_str(), crate::de::Value::new(v, false).lenient())),
+        ))
+        .map_err(Into::into)
+    }
+
+    #[cfg(feature = "serde")]
+    fn params<T>(&self) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let params = self
+            .extensions()
+            .get::<crate::Params>()
+            .ok_or_else(|| anyhow::anyhow!("Missing Params"))?;
+
+        T::deserialize(serde::de::value::MapDeserializer::new(params.iter().map(
+            |(name, value, kind)| {
+                (
+                    name,
+                    crate::de::Value::new(value, kind == crate::ParamKind::Int),
+                )
+            },
+        )))
+        .map_err(Into::into)
+    }
+
     #[cfg(feature = "multipart")]
     fn multipart(self) -> Result<form_data::FormData<Body>> {
         let m = self
             .content_type()
-            .filter(|m| m.type_() == mime::APPLICATION && m.subtype() == mime::MULTIPART)
-            .ok_or_else(|| anyhow::anyhow!("Content-Type is not Multipart"))?;
+            .filter(|m| m.type_() == mime::MULTIPART && m.subtype() == mime::FORM_DATA)
+            .ok_or(Error::UnsupportedMediaType)?;
 
         let b = m
             .get_param(mime::BOUNDARY)
@@ -175,20 +1021,139 @@ impl RequestExt for Request<Body> {
             .and_then(|jar| jar.get(name.as_ref()).cloned())
     }
 
-    #[cfg(feature = "ws")]
-    fn ws() -> Result<()> {
-        todo!()
+    #[cfg(feature = "cookie")]
+    fn parse_cookies(&self) -> cookie::CookieJar {
+        let mut jar = cookie::CookieJar::new();
+        for value in self.headers_all::<String>(header::COOKIE) {
+            for pair in value.split(';').map(str::trim).filter(|p| !p.is_empty()) {
+                if let Ok(cookie) = cookie::Cookie::parse_encoded(pair.to_owned()) {
+                    jar.add_original(cookie);
+                }
+            }
+        }
+        jar
     }
 
-    #[cfg(feature = "sse")]
-    fn sse() -> Result<()> {
+    #[cfg(feature = "cookie-secure")]
+    fn signed_cookie(
+        &mut self,
+        key: &cookie::Key,
+        name: impl AsRef<str>,
+    ) -> Option<cookie::Cookie<'static>> {
+        self.cookie_jar().ok()?.signed(key).get(name.as_ref())
+    }
+
+    #[cfg(feature = "cookie-secure")]
+    fn private_cookie(
+        &mut self,
+        key: &cookie::Key,
+        name: impl AsRef<str>,
+    ) -> Option<cookie::Cookie<'static>> {
+        self.cookie_jar().ok()?.private(key).get(name.as_ref())
+    }
+
+    #[cfg(feature = "ws")]
+    fn ws() -> Result<()> {
         todo!()
     }
 }
 
+/// The charsets [`RequestExt::text`] decodes
+enum Charset {
+    Utf8,
+    Ascii,
+    Latin1,
+}
+
+impl Charset {
+    fn of(req: &Request<Body>) -> Result<Self> {
+        let content_type = req.content_type();
+        let charset = content_type
+            .as_ref()
+            .and_then(|m| m.get_param(mime::CHARSET));
+        match charset.map(|c| c.as_str().to_ascii_lowercase()).as_deref() {
+            None | Some("utf-8") | Some("utf8") => Ok(Self::Utf8),
+            Some("us-ascii") | Some("ascii") => Ok(Self::Ascii),
+            Some("iso-8859-1") | Some("latin1") => Ok(Self::Latin1),
+            Some(_) => Err(Error::UnsupportedMediaType.into()),
+        }
+    }
+}
+
+/// Reads the body within the [`BodyLimit`] of the request, rejecting a larger declared
+/// `Content-Length` before reading anything.
+///
+/// With the `decompress` feature, the body is inflated per its `Content-Encoding` and the
+/// limit applies to the inflated size.
+async fn limited_body(req: Request<Body>) -> Result<bytes::Bytes> {
+    let BodyLimit(limit) = req.extensions().get().copied().unwrap_or_default();
+    if req.content_length().is_some_and(|n| n > limit as u64) {
+        return Err(Error::PayloadTooLarge.into());
+    }
+
+    #[cfg(feature = "decompress")]
+    {
+        let mut stream = decode_body(req, limit)?;
+        let mut body = bytes::BytesMut::with_capacity(limit.min(8192));
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            if body.len() + chunk.len() > limit {
+                return Err(Error::PayloadTooLarge.into());
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body.freeze())
+    }
+
+    #[cfg(not(feature = "decompress"))]
+    Request::bytes_limited(req.into_body(), limit).await
+}
+
+/// Streams the body inflated per its `Content-Encoding`, failing with
+/// [`Error::PayloadTooLarge`] once the inflated bytes pass `limit`
+#[cfg(feature = "decompress")]
+fn decode_body(
+    req: Request<Body>,
+    limit: usize,
+) -> Result<BoxStream<'static, Result<bytes::Bytes>>> {
+    let decoders = crate::decode::Decoders::new(
+        req.headers()
+            .get_all(header::CONTENT_ENCODING)
+            .iter()
+            .filter_map(|v| v.to_str().ok()),
+        limit,
+    )?;
+    let body = req.into_body();
+
+    if decoders.is_empty() {
+        return Ok(body.map(|r| r.map_err(Into::into)).boxed());
+    }
+
+    Ok(stream::unfold(Some((body, decoders)), |state| async move {
+        let (mut body, mut decoders) = state?;
+        loop {
+            match decoders.next() {
+                Ok(Some(chunk)) => return Some((Ok(chunk), Some((body, decoders)))),
+                Ok(None) if decoders.is_done() => return None,
+                Ok(None) => {}
+                Err(e) => return Some((Err(e), None)),
+            }
+            match body.next().await {
+                Some(Ok(chunk)) => decoders.push(chunk),
+                Some(Err(e)) => return Some((Err(e.into()), None)),
+                None => decoders.close(),
+            }
+        }
+    })
+    .boxed())
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{header, Body, Method, Request, RequestExt};
+    use crate::{
+        header, Body, Error, FetchDest, FetchMode, FetchSite, IntoResponse, Method, Request,
+        RequestExt, StatusCode,
+    };
     use anyhow::Result;
     use serde::Deserialize;
 
@@ -200,6 +1165,7 @@ mod tests {
             .body(Into::<Body>::into(""))
             .unwrap();
 
+        #[allow(dead_code)]
         #[derive(Debug, Deserialize)]
         struct Query {
             offset: usize,
@@ -220,4 +1186,1113 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn query() -> Result<()> {
+        let req = Request::get("/?offset=10&limit=10").body(Body::empty())?;
+
+        #[derive(Deserialize)]
+        struct Query {
+            offset: usize,
+            limit: usize,
+        }
+
+        let query = req.query::<Query>()?;
+        assert_eq!((query.offset, query.limit), (10, 10));
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_cookies() -> Result<()> {
+        let mut req = Request::get("/")
+            .header(header::COOKIE, "viz.id=123%20321; theme=dark")
+            .body(Body::empty())?;
+
+        let parsed = req.parse_cookies();
+        let cached = req.cookie_jar()?;
+        for name in ["viz.id", "theme"] {
+            assert_eq!(parsed.get(name), cached.get(name));
+        }
+        assert_eq!(parsed.get("viz.id").map(|c| c.value()), Some("123 321"));
+        assert_eq!(parsed.iter().count(), cached.iter().count());
+
+        let req = Request::get("/")
+            .header(header::COOKIE, "a=1; ; =broken; b=2")
+            .body(Body::empty())?;
+        let names = req
+            .parse_cookies()
+            .iter()
+            .map(|c| c.name().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(names.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn accepts() {
+        let req = Request::get("/")
+            .header(header::ACCEPT, "application/*;q=0.5, text/html, nope")
+            .header(header::ACCEPT, "*/*;q=0.1, image/png;q=oops")
+            .body(Body::empty())
+            .unwrap();
+
+        let ranges = req.accepts();
+        let ranges = ranges
+            .iter()
+            .map(|(m, q)| (m.as_ref(), *q))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ranges,
+            [
+                ("text/html", 1.0),
+                ("image/png", 1.0),
+                ("application/*", 0.5),
+                ("*/*", 0.1)
+            ]
+        );
+
+        let offered = [mime::APPLICATION_JSON, mime::TEXT_HTML];
+        assert_eq!(req.preferred(&offered), Some(mime::TEXT_HTML));
+        assert_eq!(
+            req.preferred(&[mime::APPLICATION_JSON]),
+            Some(mime::APPLICATION_JSON)
+        );
+        assert_eq!(req.preferred(&[mime::TEXT_CSS]), Some(mime::TEXT_CSS));
+
+        let req = Request::get("/")
+            .header(header::ACCEPT, "text/html;q=0")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(req.preferred(&[mime::TEXT_HTML]), None);
+
+        let req = Request::get("/").body(Body::empty()).unwrap();
+        assert_eq!(req.accepts(), [(mime::STAR_STAR, 1.0)]);
+        assert_eq!(req.preferred(&offered), Some(mime::APPLICATION_JSON));
+    }
+
+    #[test]
+    fn ranges() {
+        let ranges = |range: &str, total_len: u64| {
+            Request::get("/")
+                .header(header::RANGE, range)
+                .body(Body::empty())
+                .unwrap()
+                .ranges(total_len)
+                .map(|r| r.map_err(|e| e.downcast::<Error>().unwrap()))
+        };
+
+        assert_eq!(ranges("bytes=0-0", 10), Some(Ok(vec![(0, 0)])));
+        assert_eq!(ranges("bytes=-500", 1000), Some(Ok(vec![(500, 999)])));
+        assert_eq!(ranges("bytes=-500", 100), Some(Ok(vec![(0, 99)])));
+        assert_eq!(ranges("bytes=500-", 1000), Some(Ok(vec![(500, 999)])));
+        assert_eq!(ranges("bytes=900-2000", 1000), Some(Ok(vec![(900, 999)])));
+        assert_eq!(
+            ranges("bytes=500-599, 0-99,50-150, 151-160", 1000),
+            Some(Ok(vec![(0, 160), (500, 599)]))
+        );
+        assert_eq!(ranges("bytes=0-9, 2000-", 1000), Some(Ok(vec![(0, 9)])));
+
+        let unsatisfiable = Some(Err(Error::RangeNotSatisfiable));
+        assert_eq!(ranges("bytes=1000-", 1000), unsatisfiable);
+        assert_eq!(ranges("bytes=-0", 1000), unsatisfiable);
+        assert_eq!(ranges("bytes=0-", 0), unsatisfiable);
+
+        assert_eq!(ranges("bytes=5-1", 1000), None);
+        assert_eq!(ranges("bytes=a-b", 1000), None);
+        assert_eq!(ranges("items=0-1", 1000), None);
+        let req = Request::get("/").body(Body::empty()).unwrap();
+        assert!(req.ranges(1000).is_none());
+    }
+
+    #[test]
+    fn headers_all() {
+        use std::net::IpAddr;
+
+        let req = Request::get("/")
+            .header("x-forwarded-for", "203.0.113.7")
+            .header("x-forwarded-for", "not an ip")
+            .header("x-forwarded-for", "2001:db8::1")
+            .header("x-forwarded-for", " 10.0.0.1 ")
+            .body(Body::empty())
+            .unwrap();
+
+        assert_eq!(
+            req.headers_all::<IpAddr>("x-forwarded-for"),
+            [
+                "203.0.113.7".parse::<IpAddr>().unwrap(),
+                "2001:db8::1".parse().unwrap(),
+                "10.0.0.1".parse().unwrap(),
+            ]
+        );
+        assert_eq!(
+            req.header::<IpAddr>("x-forwarded-for"),
+            "203.0.113.7".parse().ok()
+        );
+        assert!(req.headers_all::<u8>("x-missing").is_empty());
+    }
+
+    #[test]
+    fn authorization() {
+        let auth = |value: &str| {
+            Request::get("/")
+                .header(header::AUTHORIZATION, value)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        assert_eq!(
+            auth("bearer  abc.def ").bearer_token().as_deref(),
+            Some("abc.def")
+        );
+        assert_eq!(auth("Bearer ").bearer_token(), None);
+        assert_eq!(auth("Basic abc").bearer_token(), None);
+        assert_eq!(
+            Request::get("/")
+                .body(Body::empty())
+                .unwrap()
+                .bearer_token(),
+            None
+        );
+
+        assert_eq!(
+            auth("Basic asO2c2U6").basic_auth(),
+            Some(("jöse".to_owned(), String::new()))
+        );
+        assert_eq!(
+            auth("basic YWRtaW46cGE6c3M6d29yZA==").basic_auth(),
+            Some(("admin".to_owned(), "pa:ss:word".to_owned()))
+        );
+        assert_eq!(auth("Basic bm9jb2xvbg==").basic_auth(), None);
+        assert_eq!(auth("Basic not base64!").basic_auth(), None);
+        assert_eq!(auth("Bearer asO2c2U6").basic_auth(), None);
+
+        let pair =
+            |scheme: &str, credentials: &str| Some((scheme.to_owned(), credentials.to_owned()));
+        assert_eq!(
+            auth("Basic asO2c2U6").authorization(),
+            pair("Basic", "asO2c2U6")
+        );
+        assert_eq!(
+            auth(" Bearer\tabc.def ").authorization(),
+            pair("Bearer", "abc.def")
+        );
+        assert_eq!(
+            auth("HMAC-SHA256 keyId=\"k1\", signature=\"c2ln\"").authorization(),
+            pair("HMAC-SHA256", "keyId=\"k1\", signature=\"c2ln\"")
+        );
+        assert_eq!(auth("Negotiate").authorization(), None);
+    }
+
+    #[test]
+    fn header_list() {
+        let req = Request::get("/")
+            .header(header::ACCEPT, "text/html, application/json;q=0.9")
+            .header(header::ACCEPT, "*/*;q=0.1")
+            .header(
+                header::CACHE_CONTROL,
+                "no-cache=\"set-cookie, x-a\",, max-age=0 ",
+            )
+            .header(header::CACHE_CONTROL, "private=\"x\\\",y\", ")
+            .body(Body::empty())
+            .unwrap();
+
+        assert_eq!(
+            req.header_list(header::ACCEPT),
+            ["text/html", "application/json;q=0.9", "*/*;q=0.1"]
+        );
+        assert_eq!(
+            req.header_list(header::CACHE_CONTROL),
+            [
+                "no-cache=\"set-cookie, x-a\"",
+                "max-age=0",
+                "private=\"x\\\",y\""
+            ]
+        );
+        assert!(req.header_list(header::VARY).is_empty());
+    }
+
+    #[test]
+    fn is_secure() {
+        let req = |uri: &str, headers: &[(&str, &str)]| {
+            let mut req = Request::builder().uri(uri);
+            for (k, v) in headers {
+                req = req.header(*k, *v);
+            }
+            req.body(Body::empty()).unwrap()
+        };
+
+        assert!(req("https://example.com/", &[]).is_secure(false));
+        assert!(!req("/", &[]).is_secure(true));
+
+        let forwarded = req("/", &[("x-forwarded-proto", "https, http")]);
+        assert!(forwarded.is_secure(true));
+        assert!(!forwarded.is_secure(false));
+        assert!(!req("/", &[("x-forwarded-proto", "http, https")]).is_secure(true));
+
+        let forwarded = req(
+            "/",
+            &[("forwarded", "for=1.2.3.4;Proto=\"HTTPS\", proto=http")],
+        );
+        assert!(forwarded.is_secure(true));
+        assert!(!req("/", &[("forwarded", "for=1.2.3.4;proto=http")]).is_secure(true));
+    }
+
+    #[test]
+    fn host() {
+        let req = |uri: &str, hosts: &[&str]| {
+            let mut req = Request::builder().uri(uri);
+            for host in hosts {
+                req = req.header(header::HOST, *host);
+            }
+            req.body(Body::empty()).unwrap()
+        };
+
+        assert_eq!(
+            req("/", &["Example.COM:8080"]).host(),
+            Some(("example.com".to_owned(), Some(8080)))
+        );
+        assert_eq!(
+            req("/", &["example.com"]).host(),
+            Some(("example.com".to_owned(), None))
+        );
+        assert_eq!(
+            req("https://api.example.com/", &["example.com"]).host(),
+            Some(("api.example.com".to_owned(), None))
+        );
+        assert_eq!(
+            req("/", &["[2001:DB8::1]:443"]).host(),
+            Some(("[2001:db8::1]".to_owned(), Some(443)))
+        );
+        assert_eq!(
+            req("/", &["[::1]"]).host(),
+            Some(("[::1]".to_owned(), None))
+        );
+
+        assert_eq!(req("/", &[]).host(), None);
+        assert_eq!(req("/", &["a.com", "b.com"]).host(), None);
+        assert_eq!(req("/", &["a.com, b.com"]).host(), None);
+        assert_eq!(req("/", &["evil.com@a.com"]).host(), None);
+        assert_eq!(req("/", &["a.com/x"]).host(), None);
+        assert_eq!(req("/", &["a.com:99999"]).host(), None);
+        assert_eq!(req("/", &["[::g]"]).host(), None);
+        assert_eq!(req("/", &["::1"]).host(), None);
+
+        let mut injected = req("/", &[]);
+        injected.headers_mut().insert(
+            header::HOST,
+            header::HeaderValue::from_bytes(b"a.com\tSet-Cookie: x=1").unwrap(),
+        );
+        assert_eq!(injected.host(), None);
+        assert!(header::HeaderValue::from_bytes(b"a.com\r\nSet-Cookie: x=1").is_err());
+    }
+
+    #[test]
+    fn query_lenient() -> Result<()> {
+        #[derive(Debug, Deserialize)]
+        struct Filter {
+            active: bool,
+            archived: Option<bool>,
+            page: u32,
+        }
+
+        let req = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+
+        let filter = req("/?active=on&page=2").query_lenient::<Filter>()?;
+        assert!(filter.active);
+        assert_eq!(filter.archived, None);
+        assert_eq!(filter.page, 2);
+
+        for (value, expected) in [("YES", true), ("1", true), ("no", false), ("Off", false)] {
+            let uri = format!("/?active=true&archived={}&page=1", value);
+            assert_eq!(
+                req(&uri).query_lenient::<Filter>()?.archived,
+                Some(expected)
+            );
+        }
+
+        assert!(req("/?active=on&page=1").query::<Filter>().is_err());
+        assert!(req("/?active=maybe&page=1")
+            .query_lenient::<Filter>()
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn text() -> Result<()> {
+        let req = |content_type: Option<&str>, body: &'static [u8]| {
+            let mut req = Request::post("/");
+            if let Some(content_type) = content_type {
+                req = req.header(header::CONTENT_TYPE, content_type);
+            }
+            req.body(Body::from(body)).unwrap()
+        };
+
+        assert_eq!(req(None, "héllo".as_bytes()).text().await?, "héllo");
+        let latin1 = Some("text/plain; charset=ISO-8859-1");
+        assert_eq!(req(latin1, b"h\xe9llo").text().await?, "héllo");
+
+        assert!(req(None, b"h\xe9llo").text().await.is_err());
+        assert!(req(Some("text/plain; charset=us-ascii"), b"h\xe9llo")
+            .text()
+            .await
+            .is_err());
+        let err = req(Some("text/plain; charset=shift_jis"), b"")
+            .text()
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Error>(),
+            Some(&Error::UnsupportedMediaType)
+        );
+
+        assert_eq!(req(None, b"h\xe9llo").text_lossy().await?, "h\u{fffd}llo");
+
+        Ok(())
+    }
+
+    #[cfg(feature = "cookie-secure")]
+    #[test]
+    fn secure_cookies() -> Result<()> {
+        use crate::{Response, ResponseExt};
+
+        let key = cookie::Key::generate();
+        let mut res = Response::text("");
+        res.set_signed_cookie(&key, cookie::Cookie::new("session", "alice"))?;
+        res.set_private_cookie(&key, cookie::Cookie::new("secret", "42"))?;
+        let pairs = res
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .map(|v| v.to_str().unwrap().split(';').next().unwrap().to_owned())
+            .collect::<Vec<_>>();
+        assert!(pairs
+            .iter()
+            .all(|p| !p.ends_with("=alice") && !p.ends_with("=42")));
+
+        let req = |cookies: &str| {
+            Request::builder()
+                .header(header::COOKIE, cookies)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let mut valid = req(&pairs.join("; "));
+        assert_eq!(
+            valid
+                .signed_cookie(&key, "session")
+                .map(|c| c.value().to_owned()),
+            Some("alice".to_owned())
+        );
+        assert_eq!(
+            valid
+                .private_cookie(&key, "secret")
+                .map(|c| c.value().to_owned()),
+            Some("42".to_owned())
+        );
+        assert!(valid.signed_cookie(&key, "secret").is_none());
+        assert!(valid
+            .signed_cookie(&cookie::Key::generate(), "session")
+            .is_none());
+        assert!(valid.private_cookie(&key, "missing").is_none());
+
+        let tampered = pairs[0].replace("alice", "admin");
+        assert_ne!(tampered, pairs[0]);
+        assert!(req(&tampered).signed_cookie(&key, "session").is_none());
+
+        let mut secret = pairs[1].clone();
+        let last = if secret.pop() == Some('A') { 'B' } else { 'A' };
+        secret.push(last);
+        assert!(req(&secret).private_cookie(&key, "secret").is_none());
+        assert!(req("session=alice")
+            .signed_cookie(&key, "session")
+            .is_none());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "csrf")]
+    #[tokio::test]
+    async fn verify_csrf() -> Result<()> {
+        use crate::{Response, ResponseExt};
+
+        let mut res = Response::new(Body::empty());
+        let token = res.set_csrf_token(b"secret")?;
+        let cookie = res.headers()[header::SET_COOKIE].to_str()?;
+        let cookie = cookie.split(';').next().unwrap().to_owned();
+
+        let request = |submitted: &str, form: bool| {
+            let builder = Request::post("/").header(header::COOKIE, &cookie);
+            if form {
+                builder
+                    .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+                    .body(Body::from(format!("name=x&csrf_token={}", submitted)))
+            } else {
+                builder
+                    .header("x-csrf-token", submitted)
+                    .body(Body::empty())
+            }
+        };
+
+        assert!(request(&token, false)?.verify_csrf(b"secret").await);
+        assert!(!request(&token, false)?.verify_csrf(b"other").await);
+        assert!(!request("0123", false)?.verify_csrf(b"secret").await);
+
+        let mut req = request(&token, true)?;
+        assert!(req.verify_csrf(b"secret").await);
+        assert_eq!(
+            hyper::body::to_bytes(req.into_body()).await?,
+            format!("name=x&csrf_token={}", token)
+        );
+        assert!(!request("0123", true)?.verify_csrf(b"secret").await);
+
+        let mut req = Request::post("/")
+            .header("x-csrf-token", &token)
+            .body(Body::empty())?;
+        assert!(!req.verify_csrf(b"secret").await);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "sync")]
+    #[tokio::test]
+    async fn pipe_to() -> Result<()> {
+        use tokio::sync::mpsc;
+
+        let body = || {
+            let chunks = ["a", "bc", "def"].map(Ok::<_, std::io::Error>);
+            Body::wrap_stream(futures_util::stream::iter(chunks))
+        };
+
+        let (tx, mut rx) = mpsc::channel(1);
+        let consumer = tokio::spawn(async move {
+            let mut chunks = Vec::new();
+            while let Some(chunk) = rx.recv().await {
+                chunks.push(chunk);
+            }
+            chunks
+        });
+        Request::post("/").body(body())?.pipe_to(tx).await?;
+        assert_eq!(consumer.await?, ["a", "bc", "def"]);
+
+        let (tx, rx) = mpsc::channel(1);
+        drop(rx);
+        assert!(Request::post("/").body(body())?.pipe_to(tx).await.is_err());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "cbor")]
+    #[tokio::test]
+    async fn cbor() -> Result<()> {
+        use crate::{Response, ResponseExt};
+        use std::collections::BTreeMap;
+
+        #[derive(Debug, PartialEq, Deserialize, serde::Serialize)]
+        struct Reading {
+            sensor: String,
+            values: Vec<f64>,
+            tags: BTreeMap<String, bool>,
+        }
+
+        let reading = Reading {
+            sensor: "t-01".to_owned(),
+            values: vec![21.5, -3.25],
+            tags: vec![("indoor".to_owned(), true)].into_iter().collect(),
+        };
+        let res = Response::cbor(&reading)?;
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "application/cbor");
+
+        let body = hyper::body::to_bytes(res.into_body()).await?;
+        let req = Request::post("/")
+            .header(header::CONTENT_TYPE, "application/cbor")
+            .body(Body::from(body.clone()))?;
+        assert_eq!(req.cbor::<Reading>().await?, reading);
+
+        let req = Request::post("/")
+            .header(header::CONTENT_TYPE, "application/msgpack")
+            .body(Body::from(body))?;
+        let err = req.cbor::<Reading>().await.unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&Error::UnsupportedMediaType));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[tokio::test]
+    async fn msgpack() -> Result<()> {
+        use crate::{Response, ResponseExt};
+
+        #[derive(Debug, PartialEq, Deserialize, serde::Serialize)]
+        struct Player {
+            name: String,
+            score: u32,
+        }
+
+        let player = Player {
+            name: "ferris".to_owned(),
+            score: 42,
+        };
+        let res = Response::msgpack(&player)?;
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "application/msgpack");
+
+        let body = hyper::body::to_bytes(res.into_body()).await?;
+        let req = Request::post("/")
+            .header(header::CONTENT_TYPE, "application/x-msgpack")
+            .body(Body::from(body.clone()))?;
+        assert_eq!(req.msgpack::<Player>().await?, player);
+
+        let req = Request::post("/")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body))?;
+        let err = req.msgpack::<Player>().await.unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&Error::UnsupportedMediaType));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn body_limit() -> Result<()> {
+        use crate::BodyLimit;
+
+        let body = || {
+            let chunks = ["12345", "67890"].map(Ok::<_, std::io::Error>);
+            Body::wrap_stream(futures_util::stream::iter(chunks))
+        };
+        let err = Request::bytes_limited(body(), 8).await.unwrap_err();
+        assert_eq!(err.downcast_ref::<Error>(), Some(&Error::PayloadTooLarge));
+        assert_eq!(Request::bytes_limited(body(), 10).await?, "1234567890");
+
+        let json = |limit: Option<usize>, content_length: bool| {
+            let body = "{\"name\":\"hyperstone\"}";
+            let mut req = Request::post("/").header(header::CONTENT_TYPE, "application/json");
+            if content_length {
+                req = req.header(header::CONTENT_LENGTH, body.len());
+            }
+            let mut req = req.body(Body::from(body)).unwrap();
+            if let Some(limit) = limit {
+                req.extensions_mut().insert(BodyLimit(limit));
+            }
+            req
+        };
+
+        let value = json(None, true).json::<serde_json::Value>().await?;
+        assert_eq!(value["name"], "hyperstone");
+        for content_length in [true, false] {
+            let err = json(Some(8), content_length)
+                .json::<serde_json::Value>()
+                .await
+                .unwrap_err();
+            assert_eq!(err.downcast_ref::<Error>(), Some(&Error::PayloadTooLarge));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "time")]
+    #[tokio::test]
+    async fn collect() -> Result<()> {
+        use crate::BodyConfig;
+        use std::time::Duration;
+
+        let config = BodyConfig::new()
+            .limit(8)
+            .timeout(Duration::from_millis(50));
+
+        let body = Request::new(Body::from("hello")).collect(config).await?;
+        assert_eq!(body, "hello");
+
+        let err = Request::new(Body::from("hello world"))
+            .collect(config)
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&Error::PayloadTooLarge));
+
+        let (mut sender, body) = Body::channel();
+        sender.send_data("hello".into()).await?;
+        let err = Request::new(body).collect(config).await.unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&Error::Timeout));
+        drop(sender);
+
+        Ok(())
+    }
+
+    #[test]
+    fn preferred_encoding() {
+        use crate::Encoding;
+
+        let offered = [Encoding::Gzip, Encoding::Deflate];
+        let req = |range: Option<&str>| {
+            let mut req = Request::builder().header(header::ACCEPT_ENCODING, "gzip, deflate");
+            if let Some(range) = range {
+                req = req.header(header::RANGE, range);
+            }
+            req.body(Body::empty()).unwrap()
+        };
+
+        assert_eq!(req(None).preferred_encoding(&offered), Encoding::Gzip);
+        assert_eq!(
+            req(Some("bytes=0-99")).preferred_encoding(&offered),
+            Encoding::Identity
+        );
+    }
+
+    #[test]
+    fn structured_header() {
+        use crate::structured::{BareItem, Member};
+
+        let req = Request::builder()
+            .header("cache-status", "ExampleCache; hit; ttl=376")
+            .header("cache-status", "\"CDN\"; fwd=uri-miss")
+            .header("sec-ch-ua-mobile", "?0")
+            .header("example-dict", "a=1, b")
+            .body(Body::empty())
+            .unwrap();
+
+        let list = req.structured_header("cache-status").unwrap();
+        let list = list.as_list().unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(
+            list[0].param("ttl").and_then(BareItem::as_integer),
+            Some(376)
+        );
+        assert_eq!(
+            list[1].param("fwd").and_then(BareItem::as_token),
+            Some("uri-miss")
+        );
+
+        let mobile = req.structured_header("sec-ch-ua-mobile").unwrap();
+        assert_eq!(mobile.as_item().and_then(|i| i.bare.as_bool()), Some(false));
+
+        let dict = req.structured_header("example-dict").unwrap();
+        assert_eq!(
+            dict.get("b")
+                .and_then(Member::as_item)
+                .and_then(|i| i.bare.as_bool()),
+            Some(true)
+        );
+
+        assert_eq!(req.structured_header("priority"), None);
+    }
+
+    #[test]
+    fn priority() {
+        use crate::Priority;
+
+        let req = |value: &str| {
+            Request::builder()
+                .header("priority", value)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        assert_eq!(
+            req("u=1, i").priority(),
+            Some(Priority {
+                urgency: 1,
+                incremental: true
+            })
+        );
+        assert_eq!(req("i=?0, x=5").priority(), Some(Priority::default()));
+        assert_eq!(req("u=9").priority().map(|p| p.urgency), Some(3));
+        assert_eq!(req("u=1,").priority(), None);
+        assert_eq!(Request::new(Body::empty()).priority(), None);
+    }
+
+    #[test]
+    fn is_ajax() {
+        let req = |headers: &[(&str, &str)]| {
+            let mut req = Request::get("/");
+            for (k, v) in headers {
+                req = req.header(*k, *v);
+            }
+            req.body(Body::empty()).unwrap()
+        };
+
+        assert!(req(&[("x-requested-with", "XMLHttpRequest")]).is_ajax());
+        assert!(req(&[("sec-fetch-mode", "cors"), ("sec-fetch-dest", "empty")]).is_ajax());
+        assert!(!req(&[
+            ("sec-fetch-mode", "navigate"),
+            ("sec-fetch-dest", "document")
+        ])
+        .is_ajax());
+        assert!(!req(&[("x-requested-with", "com.example.app")]).is_ajax());
+        assert!(!req(&[]).is_ajax());
+    }
+
+    #[test]
+    fn fetch_metadata() {
+        let req = |method: Method, headers: &[(&str, &str)]| {
+            let mut req = Request::builder().method(method);
+            for (k, v) in headers {
+                req = req.header(*k, *v);
+            }
+            req.body(Body::empty()).unwrap()
+        };
+
+        let metadata = req(
+            Method::POST,
+            &[
+                ("sec-fetch-site", "same-origin"),
+                ("sec-fetch-mode", "cors"),
+                ("sec-fetch-dest", "empty"),
+            ],
+        )
+        .fetch_metadata();
+        assert_eq!(metadata.site, Some(FetchSite::SameOrigin));
+        assert_eq!(metadata.mode, Some(FetchMode::Cors));
+        assert_eq!(metadata.dest, Some(FetchDest::Empty));
+        assert!(!metadata.user);
+        assert!(metadata.is_same_origin() && metadata.is_same_site());
+        assert!(metadata.resource_isolation_allows(&Method::POST));
+
+        let headers = [
+            ("sec-fetch-site", "cross-site"),
+            ("sec-fetch-mode", "navigate"),
+            ("sec-fetch-dest", "document"),
+            ("sec-fetch-user", "?1"),
+        ];
+        let metadata = req(Method::GET, &headers).fetch_metadata();
+        assert!(metadata.is_cross_site() && !metadata.is_same_site());
+        assert!(metadata.user);
+        assert!(metadata.resource_isolation_allows(&Method::GET));
+        assert!(!metadata.resource_isolation_allows(&Method::POST));
+
+        let metadata = req(
+            Method::GET,
+            &[
+                ("sec-fetch-site", "cross-site"),
+                ("sec-fetch-mode", "no-cors"),
+            ],
+        )
+        .fetch_metadata();
+        assert!(!metadata.resource_isolation_allows(&Method::GET));
+
+        let metadata = req(Method::POST, &[]).fetch_metadata();
+        assert_eq!(metadata, Default::default());
+        assert!(metadata.resource_isolation_allows(&Method::POST));
+    }
+
+    #[test]
+    fn preconditions() {
+        let req = |method: Method, name: &str, value: &str| {
+            Request::builder()
+                .method(method)
+                .header(name, value)
+                .body(Body::empty())
+                .unwrap()
+        };
+        let status = |res: Option<crate::Response<Body>>| res.map(|r| r.status());
+        let etag = Some("W/\"x\"");
+
+        let get = req(Method::GET, "if-none-match", "\"x\"");
+        let res = get.preconditions(etag).unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(res.headers()[header::ETAG], "W/\"x\"");
+        assert_eq!(status(get.preconditions(Some("\"y\""))), None);
+
+        let put = req(Method::PUT, "if-none-match", "*");
+        assert_eq!(
+            status(put.preconditions(etag)),
+            Some(StatusCode::PRECONDITION_FAILED)
+        );
+        assert_eq!(status(put.preconditions(None)), None);
+
+        let put = req(Method::PUT, "if-match", "\"x\"");
+        assert_eq!(
+            status(put.preconditions(etag)),
+            Some(StatusCode::PRECONDITION_FAILED)
+        );
+        assert_eq!(status(put.preconditions(Some("\"x\""))), None);
+
+        let put = req(Method::PUT, "if-match", "*");
+        assert_eq!(status(put.preconditions(etag)), None);
+        assert_eq!(
+            status(put.preconditions(None)),
+            Some(StatusCode::PRECONDITION_FAILED)
+        );
+    }
+
+    #[test]
+    fn content_range() {
+        let req = |range: &str, length: Option<u64>| {
+            let mut req = Request::put("/").header(header::CONTENT_RANGE, range);
+            if let Some(length) = length {
+                req = req.header(header::CONTENT_LENGTH, length);
+            }
+            req.body(Body::empty()).unwrap()
+        };
+
+        assert_eq!(
+            req("bytes 0-1023/4096", Some(1024)).content_range(),
+            Some((0, 1023, 4096))
+        );
+        assert_eq!(
+            req("bytes 4095-4095/4096", None).content_range(),
+            Some((4095, 4095, 4096))
+        );
+
+        assert_eq!(req("bytes 0-1023/4096", Some(512)).content_range(), None);
+        assert_eq!(req("bytes 1024-0/4096", None).content_range(), None);
+        assert_eq!(req("bytes 0-4096/4096", None).content_range(), None);
+        assert_eq!(req("bytes 0-1023/*", None).content_range(), None);
+        assert_eq!(req("bytes -1023/4096", None).content_range(), None);
+        assert_eq!(req("items 0-9/10", None).content_range(), None);
+        assert_eq!(req("bytes 0-1023", None).content_range(), None);
+    }
+
+    #[tokio::test]
+    async fn unsupported_media_type() {
+        #[derive(Debug, Deserialize)]
+        struct User {}
+
+        let req = || {
+            Request::builder()
+                .header(header::CONTENT_TYPE, "text/plain")
+                .body(Body::from("{}"))
+                .unwrap()
+        };
+
+        let err = req().json::<User>().await.unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&Error::UnsupportedMediaType));
+        assert_eq!(
+            err.into_response().status(),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+
+        let err = req().form::<User>().await.unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&Error::UnsupportedMediaType));
+
+        let err = req().multipart().err().unwrap();
+        assert_eq!(err.downcast_ref(), Some(&Error::UnsupportedMediaType));
+    }
+
+    #[cfg(feature = "digest")]
+    #[tokio::test]
+    async fn verify_digest() -> Result<()> {
+        let req = |name: &str, digest: &str| {
+            Request::builder()
+                .header(name, digest)
+                .body(Body::from("{\"hello\": \"world\"}\n"))
+                .unwrap()
+        };
+
+        let digest = "sha-256=:RK/0qy18MlBSVnWgjwz6lZEWjP/lF5HF9bvEF8FabDg=:";
+
+        let body = req("content-digest", digest).verify_digest().await?;
+        assert_eq!(body, "{\"hello\": \"world\"}\n");
+
+        let body = req("repr-digest", &format!("md5=:bad:, {}", digest))
+            .verify_digest()
+            .await?;
+        assert_eq!(body.len(), 19);
+
+        let err = req(
+            "content-digest",
+            "sha-256=:AAAAqy18MlBSVnWgjwz6lZEWjP/lF5HF9bvEF8FabDg=:",
+        )
+        .verify_digest()
+        .await
+        .unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&Error::DigestMismatch));
+
+        let err = Request::new(Body::from("{}"))
+            .verify_digest()
+            .await
+            .unwrap_err();
+        assert_eq!(err.into_response().status(), StatusCode::BAD_REQUEST);
+
+        let err = req("content-digest", "md5=:bad:")
+            .verify_digest()
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&Error::DigestMismatch));
+
+        let mut large = req("content-digest", digest);
+        large.extensions_mut().insert(crate::BodyLimit(8));
+        let err = large.verify_digest().await.unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&Error::PayloadTooLarge));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "jwt")]
+    #[test]
+    fn jwt_claims() -> Result<()> {
+        use hmac::{Mac, NewMac};
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Claims {
+            sub: String,
+        }
+
+        let key = b"secret";
+        let sign = |claims: serde_json::Value| {
+            let encode = |s: &[u8]| base64::encode_config(s, base64::URL_SAFE_NO_PAD);
+            let message = format!(
+                "{}.{}",
+                encode(br#"{"alg":"HS256","typ":"JWT"}"#),
+                encode(claims.to_string().as_bytes())
+            );
+            let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(key).unwrap();
+            mac.update(message.as_bytes());
+            format!("{}.{}", message, encode(&mac.finalize().into_bytes()))
+        };
+        let req = |token: &str| {
+            Request::builder()
+                .header(header::AUTHORIZATION, format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+        let token = sign(serde_json::json!({ "sub": "1234", "exp": now + 60 }));
+        let claims = req(&token).jwt_claims::<Claims>(key)?;
+        assert_eq!(claims, Claims { sub: "1234".into() });
+
+        let err = req(&token).jwt_claims::<Claims>(b"other").unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&Error::Unauthorized));
+
+        let token = sign(serde_json::json!({ "sub": "1234", "exp": now - 60 }));
+        let err = req(&token).jwt_claims::<Claims>(key).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&Error::Unauthorized));
+
+        let token = sign(serde_json::json!({ "sub": "1234", "exp": now + 60 }));
+        let mut parts = token.split('.').map(str::to_owned).collect::<Vec<_>>();
+        parts[1] = base64::encode_config(
+            serde_json::json!({ "sub": "admin", "exp": now + 60 }).to_string(),
+            base64::URL_SAFE_NO_PAD,
+        );
+        let err = req(&parts.join(".")).jwt_claims::<Claims>(key).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&Error::Unauthorized));
+        assert_eq!(err.into_response().status(), StatusCode::UNAUTHORIZED);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "decompress")]
+    #[tokio::test]
+    async fn decoded_stream() -> Result<()> {
+        use futures_util::stream::{self, TryStreamExt};
+        use std::io::Write;
+
+        let data = "hello world\n".repeat(1024);
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        encoder.write_all(data.as_bytes())?;
+        let gzipped = encoder.finish()?;
+
+        let chunks = gzipped
+            .chunks(16)
+            .map(|c| Ok::<_, std::io::Error>(c.to_vec()))
+            .collect::<Vec<_>>();
+        assert!(chunks.len() > 1);
+
+        let req = Request::builder()
+            .header(header::CONTENT_ENCODING, "gzip")
+            .body(Body::wrap_stream(stream::iter(chunks)))
+            .unwrap();
+
+        let decoded = req
+            .decoded_stream()?
+            .map_ok(|b| b.to_vec())
+            .try_concat()
+            .await?;
+        assert_eq!(decoded, data.as_bytes());
+
+        let req = Request::builder()
+            .header(header::CONTENT_ENCODING, "zstd")
+            .body(Body::empty())
+            .unwrap();
+        assert!(req.decoded_stream().is_err());
+
+        Ok(())
+    }
+
+    #[cfg(all(feature = "decompress", feature = "json"))]
+    #[tokio::test]
+    async fn decompression_bomb() -> Result<()> {
+        use crate::BodyLimit;
+        use futures_util::StreamExt;
+        use std::io::Write;
+
+        let mut br = brotli::CompressorWriter::new(Vec::new(), 4096, 1, 22);
+        for _ in 0..16 {
+            br.write_all(&[0; 1 << 20])?;
+        }
+        let bomb = br.into_inner();
+        assert!(bomb.len() < 16 * 1024);
+
+        let req = || {
+            Request::builder()
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::CONTENT_ENCODING, "br")
+                .body(Body::from(bomb.clone()))
+                .unwrap()
+        };
+
+        let mut stream = req().decoded_stream()?;
+        let chunk = stream.next().await.unwrap()?;
+        assert!(chunk.len() <= 8 * 1024);
+
+        let mut limited = req();
+        limited.extensions_mut().insert(BodyLimit(1 << 20));
+        let err = limited.json::<serde_json::Value>().await.unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&Error::PayloadTooLarge));
+        assert_eq!(err.into_response().status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        Ok(())
+    }
+
+    #[cfg(all(feature = "decompress", feature = "json"))]
+    #[tokio::test]
+    async fn json_gzip() -> Result<()> {
+        use crate::BodyLimit;
+        use std::io::Write;
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Point {
+            x: i32,
+            y: i32,
+        }
+
+        let gzip = |data: &[u8]| -> Result<Vec<u8>> {
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+            encoder.write_all(data)?;
+            Ok(encoder.finish()?)
+        };
+        let req = |encoding: &str, body: Vec<u8>| {
+            Request::builder()
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::CONTENT_ENCODING, encoding)
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let body = gzip(br#"{"x":1,"y":2}"#)?;
+        assert_eq!(
+            req("gzip", body).json::<Point>().await?,
+            Point { x: 1, y: 2 }
+        );
+
+        let body = gzip(&gzip(br#"{"x":3,"y":4}"#)?)?;
+        assert_eq!(
+            req("gzip, identity, gzip", body).json::<Point>().await?,
+            Point { x: 3, y: 4 }
+        );
+
+        let err = req("zstd", Vec::new()).json::<Point>().await.unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&Error::UnsupportedMediaType));
+
+        let mut bomb = req("gzip", gzip(&vec![b' '; 4096])?);
+        bomb.extensions_mut().insert(BodyLimit(1024));
+        let err = bomb.json::<Point>().await.unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&Error::PayloadTooLarge));
+
+        Ok(())
+    }
 }
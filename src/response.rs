@@ -1,9 +1,239 @@
 use crate::{
     anyhow::Result,
+    async_trait,
     header::{self, HeaderValue},
-    Body, Response, StatusCode,
+    Body, Error, Method, RequestExt, Response, StatusCode,
 };
 
+use std::{
+    ops::RangeInclusive,
+    time::{Duration, SystemTime},
+};
+
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+/// A part of a [`ResponseExt::multipart`] body
+#[derive(Clone, Debug, Default)]
+pub struct BodyPart {
+    headers: header::HeaderMap,
+    body: bytes::Bytes,
+}
+
+impl BodyPart {
+    pub fn new(body: impl Into<bytes::Bytes>) -> Self {
+        Self {
+            headers: header::HeaderMap::new(),
+            body: body.into(),
+        }
+    }
+
+    /// Adds a header of the part, e.g. its `Content-Type` or `Content-Range`
+    pub fn header(mut self, name: header::HeaderName, value: HeaderValue) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    /// Writes the part framed by the delimiter of the boundary
+    fn encode(&self, boundary: &str) -> bytes::Bytes {
+        let mut buf = bytes::BytesMut::with_capacity(self.body.len() + boundary.len() + 64);
+        buf.extend_from_slice(b"--");
+        buf.extend_from_slice(boundary.as_bytes());
+        buf.extend_from_slice(b"\r\n");
+        for (name, value) in &self.headers {
+            buf.extend_from_slice(name.as_str().as_bytes());
+            buf.extend_from_slice(b": ");
+            buf.extend_from_slice(value.as_bytes());
+            buf.extend_from_slice(b"\r\n");
+        }
+        buf.extend_from_slice(b"\r\n");
+        buf.extend_from_slice(&self.body);
+        buf.extend_from_slice(b"\r\n");
+        buf.freeze()
+    }
+}
+
+/// Serializes the items as the chunks of a JSON array
+#[cfg(feature = "json")]
+fn json_array<S, T, E>(
+    items: S,
+) -> impl futures_util::Stream<Item = Result<bytes::Bytes, Box<dyn std::error::Error + Send + Sync>>>
+       + Send
+       + 'static
+where
+    S: futures_util::Stream<Item = Result<T, E>> + Send + 'static,
+    T: serde::Serialize + Send + 'static,
+    E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+{
+    use futures_util::{stream, StreamExt};
+
+    stream::unfold(Some((items.boxed(), false)), |state| async move {
+        let (mut items, opened) = state?;
+        let item = match items.next().await {
+            Some(Ok(item)) => item,
+            Some(Err(e)) => return Some((Err(e.into()), None)),
+            None => {
+                let close = if opened { &b"]"[..] } else { &b"[]"[..] };
+                return Some((Ok(bytes::Bytes::from_static(close)), None));
+            }
+        };
+
+        let mut chunk = vec![if opened { b',' } else { b'[' }];
+        match serde_json::to_writer(&mut chunk, &item) {
+            Ok(()) => Some((Ok(chunk.into()), Some((items, true)))),
+            Err(e) => Some((Err(e.into()), None)),
+        }
+    })
+}
+
+/// A CSV record terminated by CRLF, quoting fields containing commas, quotes or line breaks
+fn csv_record<R, F>(fields: R) -> bytes::Bytes
+where
+    R: IntoIterator<Item = F>,
+    F: AsRef<str>,
+{
+    let mut line = Vec::new();
+    for (i, field) in fields.into_iter().enumerate() {
+        let field = field.as_ref();
+        if i > 0 {
+            line.push(b',');
+        }
+        if field.contains(&[',', '"', '\r', '\n'][..]) {
+            line.push(b'"');
+            line.extend_from_slice(field.replace('"', "\"\"").as_bytes());
+            line.push(b'"');
+        } else {
+            line.extend_from_slice(field.as_bytes());
+        }
+    }
+    line.extend_from_slice(b"\r\n");
+    line.into()
+}
+
+/// A random boundary of 32 hex digits
+fn random_boundary() -> String {
+    use std::{
+        collections::hash_map::RandomState,
+        hash::{BuildHasher, Hasher},
+    };
+
+    let hash = || RandomState::new().build_hasher().finish();
+    format!("{:016x}{:016x}", hash(), hash())
+}
+
+/// The `Content-Disposition` value of an attachment saved as `filename`
+fn attachment(filename: &str) -> String {
+    let fallback = filename
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' => format!("\\{}", c),
+            ' '..='~' => c.to_string(),
+            _ => "_".to_owned(),
+        })
+        .collect::<String>();
+    let mut value = format!("attachment; filename=\"{}\"", fallback);
+
+    if filename.bytes().any(|b| !(b' '..=b'~').contains(&b)) {
+        value.push_str("; filename*=UTF-8''");
+        for b in filename.bytes() {
+            match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => value.push(b as char),
+                b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|'
+                | b'~' => value.push(b as char),
+                _ => value.push_str(&format!("%{:02X}", b)),
+            }
+        }
+    }
+    value
+}
+
+/// A `Link` header entry, `<url>; params`, failing when the URL would break out of the `<>`
+fn link(url: &str, params: &str) -> Result<String> {
+    anyhow::ensure!(
+        !url.is_empty()
+            && url
+                .bytes()
+                .all(|b| b.is_ascii_graphic() && b != b'<' && b != b'>'),
+        "invalid link target `{}`",
+        url
+    );
+    Ok(format!("<{}>; {}", url, params))
+}
+
+/// Converts a value into a response
+pub trait IntoResponse {
+    fn into_response(self) -> Response<Body>;
+}
+
+impl IntoResponse for Response<Body> {
+    fn into_response(self) -> Response<Body> {
+        self
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response<Body> {
+        self.into()
+    }
+}
+
+/// Responds the status of a crate [`Error`], otherwise `500 Internal Server Error`
+impl IntoResponse for anyhow::Error {
+    fn into_response(self) -> Response<Body> {
+        match self.downcast::<Error>() {
+            Ok(e) => e.into_response(),
+            Err(_) => {
+                let mut res = Response::default();
+                *res.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                res
+            }
+        }
+    }
+}
+
+/// Nested results flatten, so in `Result<Result<Response<Body>>>` an error of either layer
+/// responds as an [`anyhow::Error`] does
+impl<T: IntoResponse> IntoResponse for Result<T> {
+    fn into_response(self) -> Response<Body> {
+        match self {
+            Ok(t) => t.into_response(),
+            Err(e) => e.into_response(),
+        }
+    }
+}
+
+/// Turns a nested handler result into the `Result<Response<Body>>` a [`Handler`] returns,
+/// responding errors of the inner layers right away.
+///
+/// [`Handler`]: crate::Handler
+pub fn flatten_response<T: IntoResponse>(result: Result<T>) -> Result<Response<Body>> {
+    result.map(IntoResponse::into_response)
+}
+
+/// The value of a `Retry-After` header
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RetryAfter {
+    /// Sent as whole seconds, rounded up
+    Delay(Duration),
+    /// Sent as an HTTP-date
+    Date(SystemTime),
+}
+
+impl From<RetryAfter> for HeaderValue {
+    fn from(retry_after: RetryAfter) -> Self {
+        match retry_after {
+            RetryAfter::Delay(delay) => {
+                let secs = delay.as_secs() + u64::from(delay.subsec_nanos() > 0);
+                HeaderValue::from(secs)
+            }
+            RetryAfter::Date(date) => {
+                HeaderValue::from_str(&httpdate::fmt_http_date(date)).unwrap()
+            }
+        }
+    }
+}
+
+#[async_trait]
 pub trait ResponseExt {
     /// Responds TEXT
     fn text(data: impl Into<Body>) -> Response<Body> {
@@ -26,6 +256,141 @@ pub trait ResponseExt {
             .map_err(Into::into)
     }
 
+    /// Responds MessagePack, as `application/msgpack`
+    #[cfg(feature = "msgpack")]
+    fn msgpack<T>(data: T) -> Result<Response<Body>>
+    where
+        T: serde::Serialize,
+    {
+        rmp_serde::to_vec_named(&data)
+            .map(|v| Self::with(v, "application/msgpack"))
+            .map_err(Into::into)
+    }
+
+    /// Responds CBOR, as `application/cbor`
+    #[cfg(feature = "cbor")]
+    fn cbor<T>(data: T) -> Result<Response<Body>>
+    where
+        T: serde::Serialize,
+    {
+        let mut body = Vec::new();
+        ciborium::ser::into_writer(&data, &mut body)?;
+        Ok(Self::with(body, "application/cbor"))
+    }
+
+    /// Responds JSON with the status, e.g. `201 Created` or `422 Unprocessable Entity`
+    #[cfg(feature = "json")]
+    fn json_with_status<T>(data: T, status: StatusCode) -> Result<Response<Body>>
+    where
+        T: serde::Serialize,
+    {
+        let mut res = Self::json(data)?;
+        *res.status_mut() = status;
+        Ok(res)
+    }
+
+    /// Streams the items as a JSON array, serializing each as it arrives. An error ends the
+    /// body early, leaving the array unterminated.
+    #[cfg(feature = "json")]
+    fn json_array_stream<S, T, E>(items: S) -> Response<Body>
+    where
+        S: futures_util::Stream<Item = std::result::Result<T, E>> + Send + 'static,
+        T: serde::Serialize + Send + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        Self::stream(json_array(items), mime::APPLICATION_JSON.as_ref())
+    }
+
+    /// Streams the items as a JSON array like [`json_array_stream`](Self::json_array_stream),
+    /// gzip-compressed when the request accepts it. Each item is flushed, so the client can
+    /// decode the items received so far.
+    #[cfg(all(feature = "json", feature = "compress"))]
+    fn json_array_stream_compressed<S, T, E>(req: &crate::Request<Body>, items: S) -> Response<Body>
+    where
+        S: futures_util::Stream<Item = std::result::Result<T, E>> + Send + 'static,
+        T: serde::Serialize + Send + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        use crate::{encode, Encoding};
+
+        let encoding = req.preferred_encoding(&[Encoding::Gzip]);
+        let mut res = match encode::Encoder::new(encoding) {
+            Some(encoder) => {
+                let body = encode::encode_stream(json_array(items), encoder);
+                let mut res = Self::stream(body, mime::APPLICATION_JSON.as_ref());
+                res.headers_mut().insert(
+                    header::CONTENT_ENCODING,
+                    HeaderValue::from_static(encoding.as_str()),
+                );
+                res
+            }
+            None => Self::json_array_stream(items),
+        };
+        res.headers_mut()
+            .insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+        res
+    }
+
+    /// Responds JSON with only the requested top-level fields, as in JSON:API sparse
+    /// fieldsets, of the object or of each object in an array. `None` keeps all fields.
+    #[cfg(feature = "json")]
+    fn json_sparse<T>(data: T, fields: Option<&[&str]>) -> Result<Response<Body>>
+    where
+        T: serde::Serialize,
+    {
+        let mut value = serde_json::to_value(data)?;
+        if let Some(fields) = fields {
+            let prune = |value: &mut serde_json::Value| {
+                if let Some(object) = value.as_object_mut() {
+                    object.retain(|key, _| fields.contains(&key.as_str()));
+                }
+            };
+            match &mut value {
+                serde_json::Value::Array(items) => items.iter_mut().for_each(prune),
+                value => prune(value),
+            }
+        }
+        Self::json(value)
+    }
+
+    /// Serializes to the format the request's `Accept` header prefers among the enabled
+    /// ones, setting the matching `Content-Type`: JSON with the `json` feature, MessagePack
+    /// with `msgpack` and CBOR with `cbor`, preferred in that order on ties.
+    ///
+    /// Fails with [`Error::NotAcceptable`] when no enabled format is acceptable.
+    #[cfg(feature = "serde")]
+    fn auto_serialize<T>(req: &crate::Request<Body>, data: T) -> Result<Response<Body>>
+    where
+        T: serde::Serialize,
+    {
+        let offered = [
+            #[cfg(feature = "json")]
+            mime::APPLICATION_JSON,
+            #[cfg(feature = "msgpack")]
+            "application/msgpack".parse::<mime::Mime>()?,
+            #[cfg(feature = "cbor")]
+            "application/cbor".parse::<mime::Mime>()?,
+        ];
+
+        let accept = req
+            .headers()
+            .get(header::ACCEPT)
+            .and_then(|v| v.to_str().ok());
+
+        match crate::negotiation::negotiate(accept, &offered) {
+            #[cfg(feature = "json")]
+            Some(m) if m == mime::APPLICATION_JSON => Self::json(data),
+            #[cfg(feature = "msgpack")]
+            Some(m) if m.subtype() == "msgpack" => Self::msgpack(data),
+            #[cfg(feature = "cbor")]
+            Some(m) if m.subtype() == "cbor" => Self::cbor(data),
+            _ => {
+                let _ = data;
+                Err(Error::NotAcceptable.into())
+            }
+        }
+    }
+
     /// Responds body with `Content-Type`
     fn with(data: impl Into<Body>, ct: &'static str) -> Response<Body> {
         let mut res = Response::new(data.into());
@@ -34,6 +399,176 @@ pub trait ResponseExt {
         res
     }
 
+    /// Responds the body as a download with `Content-Type`, saved as `filename`.
+    ///
+    /// Non-ASCII names are sent in the `filename*` parameter, percent-encoded as UTF-8 per
+    /// RFC 6266, after an ASCII `filename` fallback for older clients.
+    fn download(data: impl Into<Body>, filename: &str, ct: &'static str) -> Response<Body> {
+        let mut res = Self::with(data, ct);
+        res.headers_mut().insert(
+            header::CONTENT_DISPOSITION,
+            HeaderValue::from_str(&attachment(filename)).unwrap(),
+        );
+        res
+    }
+
+    /// Streams the chunks as the body with `Content-Type`, without a `Content-Length`, so
+    /// HTTP/1.1 sends it chunked
+    fn stream<S, E>(stream: S, ct: &'static str) -> Response<Body>
+    where
+        S: futures_util::Stream<Item = std::result::Result<bytes::Bytes, E>> + Send + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        Self::with(Body::wrap_stream(stream), ct)
+    }
+
+    /// Streams the rows as `text/csv`, each record written as it arrives, after a header
+    /// record of the column names unless they are empty.
+    ///
+    /// Fields containing commas, quotes or line breaks are quoted, and records end in CRLF,
+    /// as in RFC 4180.
+    fn csv_stream<S, R, F, E>(headers: &[&str], rows: S) -> Response<Body>
+    where
+        S: futures_util::Stream<Item = std::result::Result<R, E>> + Send + 'static,
+        R: IntoIterator<Item = F> + 'static,
+        F: AsRef<str> + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        use futures_util::{stream, StreamExt, TryStreamExt};
+
+        let header = (!headers.is_empty()).then(|| Ok(csv_record(headers)));
+        let records = rows.map_ok(csv_record).map_err(Into::into);
+        Self::stream(
+            stream::iter(header).chain(records),
+            "text/csv; charset=utf-8",
+        )
+    }
+
+    /// Streams the chunks like [`stream`](Self::stream), declaring
+    /// `Transfer-Encoding: chunked` explicitly.
+    ///
+    /// Any `Content-Length` is dropped, as the two must not be sent together. HTTP/2 has no
+    /// chunked coding, and hyper leaves the header out there.
+    fn chunked<S, E>(stream: S, ct: &'static str) -> Response<Body>
+    where
+        S: futures_util::Stream<Item = std::result::Result<bytes::Bytes, E>> + Send + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let mut res = Self::stream(stream, ct);
+        let headers = res.headers_mut();
+        headers.remove(header::CONTENT_LENGTH);
+        headers.insert(
+            header::TRANSFER_ENCODING,
+            HeaderValue::from_static("chunked"),
+        );
+        res
+    }
+
+    /// Streams the events as `text/event-stream`, with `Cache-Control: no-cache`
+    #[cfg(feature = "sse")]
+    fn event_stream<S, E>(stream: S) -> Response<Body>
+    where
+        S: futures_util::Stream<Item = std::result::Result<crate::sse::Event, E>> + Send + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        use futures_util::TryStreamExt;
+
+        let mut res = Self::stream(stream.map_ok(|event| event.encode()), "text/event-stream");
+        res.headers_mut()
+            .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        res
+    }
+
+    /// Streams the reader as the body with `Content-Type`
+    fn from_reader<R>(reader: R, ct: &'static str) -> Response<Body>
+    where
+        R: futures_util::io::AsyncRead + Send + 'static,
+    {
+        use futures_util::{io::AsyncReadExt, stream};
+
+        let body = stream::unfold(Some(Box::pin(reader)), |reader| async move {
+            let mut reader = reader?;
+            let mut buf = vec![0; 8192];
+            match reader.read(&mut buf).await {
+                Ok(0) => None,
+                Ok(n) => {
+                    buf.truncate(n);
+                    Some((Ok(bytes::Bytes::from(buf)), Some(reader)))
+                }
+                Err(e) => Some((Err(e), None)),
+            }
+        });
+
+        Self::with(Body::wrap_stream(body), ct)
+    }
+
+    /// Streams the parts as a `multipart/{subtype}` body, e.g. `mixed` or `byteranges`,
+    /// delimited by the boundary, or a random one when `None`.
+    ///
+    /// Fails when the boundary is longer than 70 characters or contains characters RFC 2046
+    /// does not allow.
+    fn multipart<S>(subtype: &str, parts: S, boundary: Option<&str>) -> Result<Response<Body>>
+    where
+        S: futures_util::Stream<Item = Result<BodyPart>> + Send + 'static,
+    {
+        use futures_util::{stream, StreamExt};
+
+        let boundary = match boundary {
+            Some(boundary) => {
+                let valid = (1..=70).contains(&boundary.len())
+                    && !boundary.ends_with(' ')
+                    && boundary
+                        .bytes()
+                        .all(|b| b.is_ascii_alphanumeric() || b"'()+_,-./:=? ".contains(&b));
+                if !valid {
+                    anyhow::bail!("invalid multipart boundary `{}`", boundary);
+                }
+                boundary.to_owned()
+            }
+            None => random_boundary(),
+        };
+
+        let content_type =
+            HeaderValue::from_str(&format!("multipart/{}; boundary=\"{}\"", subtype, boundary))?;
+        let close = bytes::Bytes::from(format!("--{}--\r\n", boundary));
+        let body = parts
+            .map(move |part| part.map(|part| part.encode(&boundary)))
+            .chain(stream::once(async move { Ok(close) }));
+
+        let mut res = Response::new(Body::wrap_stream(body));
+        res.headers_mut().insert(header::CONTENT_TYPE, content_type);
+        Ok(res)
+    }
+
+    /// Responds `206 Partial Content` with the range of the in-memory data, sliced without
+    /// copying. The end is clamped to the data.
+    ///
+    /// Responds `416 Range Not Satisfiable` when the range starts past the data or is empty.
+    fn partial_bytes(data: bytes::Bytes, range: RangeInclusive<u64>) -> Response<Body> {
+        let len = data.len() as u64;
+        let (first, last) = (*range.start(), (*range.end()).min(len.saturating_sub(1)));
+
+        let mut res = Response::default();
+        if first >= len || first > last {
+            *res.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
+            res.headers_mut().insert(
+                header::CONTENT_RANGE,
+                HeaderValue::from_str(&format!("bytes */{}", len)).unwrap(),
+            );
+            return res;
+        }
+
+        *res.status_mut() = StatusCode::PARTIAL_CONTENT;
+        res.headers_mut().insert(
+            header::CONTENT_RANGE,
+            HeaderValue::from_str(&format!("bytes {}-{}/{}", first, last, len)).unwrap(),
+        );
+        res.headers_mut()
+            .insert(header::CONTENT_LENGTH, HeaderValue::from(last - first + 1));
+        *res.body_mut() = Body::from(data.slice(first as usize..=last as usize));
+        res
+    }
+
     /// Sets the `Content-Location` header
     fn location(location: &'static str) -> Response<Body> {
         let mut res = Response::default();
@@ -51,37 +586,894 @@ pub trait ResponseExt {
         res
     }
 
+    /// Settles an `Expect: 100-continue` request after inspecting its headers, failing
+    /// with [`Error::ExpectationFailed`] when `accept` is `false` so that `?` responds `417`.
+    ///
+    /// hyper sends the interim `100 Continue` by itself, the first time the body is polled,
+    /// so accepting only means going on to read the body. Rejecting responds before
+    /// reading it, and the client never sends it. Requests without the expectation always
+    /// pass, their body being on its way already.
+    fn continue_or_reject(req: &crate::Request<Body>, accept: bool) -> Result<()> {
+        if accept || !req.expects_continue() {
+            Ok(())
+        } else {
+            Err(Error::ExpectationFailed.into())
+        }
+    }
+
+    /// Responds `304 Not Modified` with the current entity tag
+    fn not_modified(etag: &str) -> Result<Response<Body>> {
+        let mut res = Response::default();
+        *res.status_mut() = StatusCode::NOT_MODIFIED;
+        res.headers_mut()
+            .insert(header::ETAG, HeaderValue::from_str(etag)?);
+        Ok(res)
+    }
+
+    /// Responds `201 Created` with the `Location` of the new resource
+    fn created(location: &'static str) -> Response<Body> {
+        let mut res = Response::default();
+        *res.status_mut() = StatusCode::CREATED;
+        res.headers_mut()
+            .insert(header::LOCATION, HeaderValue::from_static(location));
+        res
+    }
+
+    /// Responds `202 Accepted`
+    fn accepted() -> Response<Body> {
+        let mut res = Response::default();
+        *res.status_mut() = StatusCode::ACCEPTED;
+        res
+    }
+
+    /// Responds `204 No Content`, without a body or `Content-Type`
+    fn no_content() -> Response<Body> {
+        let mut res = Response::default();
+        *res.status_mut() = StatusCode::NO_CONTENT;
+        res
+    }
+
+    /// Responds `400 Bad Request`
+    fn bad_request() -> Response<Body> {
+        let mut res = Response::default();
+        *res.status_mut() = StatusCode::BAD_REQUEST;
+        res
+    }
+
+    /// Responds `404 Not Found`
+    fn not_found() -> Response<Body> {
+        let mut res = Response::default();
+        *res.status_mut() = StatusCode::NOT_FOUND;
+        res
+    }
+
+    /// Responds `412 Precondition Failed`
+    fn precondition_failed() -> Response<Body> {
+        let mut res = Response::default();
+        *res.status_mut() = StatusCode::PRECONDITION_FAILED;
+        res
+    }
+
+    /// Responds `503 Service Unavailable`, telling when to retry if known
+    fn service_unavailable(retry_after: Option<RetryAfter>) -> Response<Body> {
+        let mut res = Response::default();
+        *res.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+        if let Some(retry_after) = retry_after {
+            res.headers_mut()
+                .insert(header::RETRY_AFTER, retry_after.into());
+        }
+        res
+    }
+
+    /// Responds `401 Unauthorized` with a `WWW-Authenticate` challenge, usually built with
+    /// [`Challenge`](crate::Challenge)
+    fn unauthorized(challenge: &str) -> Result<Response<Body>> {
+        let mut res = Response::default();
+        *res.status_mut() = StatusCode::UNAUTHORIZED;
+        res.headers_mut()
+            .insert(header::WWW_AUTHENTICATE, HeaderValue::from_str(challenge)?);
+        Ok(res)
+    }
+
+    /// Responds `204` to a CORS preflight from `origin`, the request's `Origin`, with the
+    /// allowed origin, methods, headers, max age and credentials of the policy. Only `Vary`
+    /// is set when the origin is not allowed.
+    fn preflight(cors: &crate::Cors, origin: Option<&str>) -> Response<Body> {
+        let mut res = Response::default();
+        *res.status_mut() = StatusCode::NO_CONTENT;
+        let mut res = res.cors(cors, origin);
+        if !res
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        {
+            return res;
+        }
+
+        let headers = res.headers_mut();
+        if !cors.methods().is_empty() {
+            let methods = cors
+                .methods()
+                .iter()
+                .map(Method::as_str)
+                .collect::<Vec<_>>();
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_METHODS,
+                HeaderValue::from_str(&methods.join(", ")).unwrap(),
+            );
+        }
+        if !cors.headers().is_empty() {
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_HEADERS,
+                HeaderValue::from_str(&cors.headers().join(", ")).unwrap(),
+            );
+        }
+        if let Some(secs) = cors.max_age_secs() {
+            headers.insert(header::ACCESS_CONTROL_MAX_AGE, HeaderValue::from(secs));
+        }
+        res
+    }
+
+    /// Responds `426 Upgrade Required` with the protocols listed in the `Upgrade` header
+    fn upgrade_required(protocols: &[&str]) -> Result<Response<Body>> {
+        let mut res = Response::default();
+        *res.status_mut() = StatusCode::UPGRADE_REQUIRED;
+        res.headers_mut().insert(
+            header::UPGRADE,
+            HeaderValue::from_str(&protocols.join(", "))?,
+        );
+        res.headers_mut()
+            .insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
+        Ok(res)
+    }
+
+    /// Streams a file with a `Content-Type` guessed from its extension
+    #[cfg(feature = "fs")]
+    async fn file(path: impl AsRef<Path> + Send) -> Result<Response<Body>> {
+        Self::file_with_progress(path, |_| {}).await
+    }
+
+    /// Streams a file, calling `progress` with the total bytes sent after each chunk.
+    ///
+    /// The callback runs inline while the body is polled, so it must be cheap and must not
+    /// block. When the client disconnects the body is dropped and the callback is not called
+    /// again, so the last total reported is less than the file size.
+    #[cfg(feature = "fs")]
+    async fn file_with_progress<F>(
+        path: impl AsRef<Path> + Send,
+        progress: F,
+    ) -> Result<Response<Body>>
+    where
+        F: FnMut(u64) + Send + 'static,
+    {
+        use futures_util::stream;
+        use tokio::io::AsyncReadExt;
+
+        let path = path.as_ref();
+        let file = tokio::fs::File::open(path).await?;
+        let len = file.metadata().await?.len();
+
+        let body = stream::unfold(Some((file, 0, progress)), |state| async move {
+            let (mut file, sent, mut progress) = state?;
+            let mut buf = bytes::BytesMut::with_capacity(8192);
+            match file.read_buf(&mut buf).await {
+                Ok(0) => None,
+                Ok(n) => {
+                    let sent = sent + n as u64;
+                    progress(sent);
+                    Some((Ok(buf.freeze()), Some((file, sent, progress))))
+                }
+                Err(e) => Some((Err(e), None)),
+            }
+        });
+
+        let mut res = Response::new(Body::wrap_stream(body));
+        res.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_str(mime_guess::from_path(path).first_or_octet_stream().as_ref())?,
+        );
+        res.headers_mut()
+            .insert(header::CONTENT_LENGTH, HeaderValue::from(len));
+        Ok(res)
+    }
+
+    /// Reorders the headers by name, keeping the order of the values of each name
+    fn canonicalize_headers(self) -> Self;
+
+    /// Passes each chunk of the body to `f` as it is streamed to the client, without
+    /// buffering the body.
+    ///
+    /// Chunks are observed in order, just before they are written. A failing body is passed
+    /// through as is, so `f` stops being called at the error and sees no partial chunk.
+    fn tee_body<F>(self, f: F) -> Self
+    where
+        F: FnMut(&bytes::Bytes) + Send + 'static;
+
+    /// Sets `Strict-Transport-Security` with the `max-age` in whole seconds
+    fn strict_transport_security(
+        self,
+        max_age: Duration,
+        include_subdomains: bool,
+        preload: bool,
+    ) -> Self;
+
+    /// Sets `Content-Security-Policy`
+    fn content_security_policy(self, policy: &crate::ContentSecurityPolicy) -> Self;
+
+    /// Sets `X-Frame-Options`
+    fn x_frame_options(self, mode: crate::FrameOptions) -> Self;
+
+    /// Sets `Referrer-Policy`
+    fn referrer_policy(self, policy: crate::ReferrerPolicy) -> Self;
+
+    /// Sets `Access-Control-Allow-Origin`, and `-Credentials` if allowed, for a request from
+    /// `origin`, the request's `Origin`, leaving them out when the origin is not allowed.
+    ///
+    /// Echoed origins add `Origin` to `Vary`, so caches keep the responses apart.
+    fn cors(self, cors: &crate::Cors, origin: Option<&str>) -> Self;
+
+    /// Rechunks the body into frames of `size` bytes, the last one possibly shorter.
+    ///
+    /// The whole body is buffered first, so this suits bodies already in memory; the frames
+    /// are then cheap slices of that buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `size` is zero.
+    fn chunked_by(self, size: usize) -> Self;
+
+    /// Compresses the body with the coding `accept_encoding`, the request's
+    /// `Accept-Encoding`, prefers among `br`, `gzip` and `deflate`, adding
+    /// `Accept-Encoding` to `Vary` and dropping the `Content-Length`. A strong `ETag` is
+    /// weakened, as the bytes differ from the identity representation.
+    ///
+    /// Responses already encoded, without content or partial, marked `Cache-Control:
+    /// no-transform`, or skipped by the default [`Compression`](crate::Compression), such
+    /// as images, video and small bodies, are left as is.
+    #[cfg(feature = "compress")]
+    fn compress(self, accept_encoding: &str) -> Self;
+
+    /// [`compress`](Self::compress) skipping the media types and sizes of `config`
+    #[cfg(feature = "compress")]
+    fn compress_with(self, accept_encoding: &str, config: &crate::Compression) -> Self;
+
+    /// Sets the status with a custom reason phrase.
+    ///
+    /// HTTP/2 and later have no reason phrases, so only the status is set for them.
+    #[cfg(feature = "http1")]
+    fn status_reason(self, code: StatusCode, reason: &str) -> Result<Self>
+    where
+        Self: Sized;
+
+    /// How long to wait before retrying per the `Retry-After` header of a received response,
+    /// in delay seconds or an HTTP-date, zero when the date has passed
+    fn retry_after(&self) -> Option<Duration>;
+
+    /// Appends a `Server-Timing` header of the metrics, each a name, its duration, sent in
+    /// milliseconds, and an optional description.
+    ///
+    /// Fails when a name is not a token, or a description contains control characters.
+    fn server_timing(self, metrics: &[(&str, Duration, Option<&str>)]) -> Result<Self>
+    where
+        Self: Sized;
+
+    /// Appends a `Link` header of the pagination URLs present, as `rel` `first`, `prev`,
+    /// `next` and `last`. Nothing is added when all are absent.
+    ///
+    /// Fails when a URL contains `<`, `>`, whitespace or control characters.
+    fn link_pagination(
+        self,
+        first: Option<&str>,
+        prev: Option<&str>,
+        next: Option<&str>,
+        last: Option<&str>,
+    ) -> Result<Self>
+    where
+        Self: Sized;
+
+    /// Appends `Link: <url>; rel=preload; as=dest` headers for the resources, each a URL and
+    /// its destination such as `style`, `script` or `font`.
+    ///
+    /// No 103 Early Hints response is sent, as hyper 0.14 cannot send informational
+    /// responses other than `100 Continue`. CDNs and proxies which support Early Hints, e.g.
+    /// Cloudflare, cache these links and send a 103 ahead of later responses.
+    ///
+    /// Fails when a URL contains `<`, `>`, whitespace or control characters, or a destination
+    /// is not a token.
+    fn preload_links(self, links: &[(&str, &str)]) -> Result<Self>
+    where
+        Self: Sized;
+
+    /// Redirects with `308 Permanent Redirect` to the HTTPS URL of the host and URI, dropping
+    /// an explicit `:80` port from the host
+    fn redirect_to_https(host: &str, uri: &crate::Uri) -> Result<Response<Body>> {
+        let host = host.strip_suffix(":80").unwrap_or(host);
+        let authority = host.parse::<crate::http::uri::Authority>()?;
+        let location = format!(
+            "https://{}{}",
+            authority,
+            uri.path_and_query().map_or("/", |p| p.as_str())
+        );
+
+        let mut res = Response::default();
+        *res.status_mut() = StatusCode::PERMANENT_REDIRECT;
+        res.headers_mut()
+            .insert(header::LOCATION, HeaderValue::from_str(&location)?);
+        Ok(res)
+    }
+
+    /// Returns the response's cookie jar, on first use holding the cookies of the
+    /// `Set-Cookie` headers already present, except removals
+    #[cfg(feature = "cookie")]
+    fn cookie_jar(&mut self) -> &mut cookie::CookieJar;
+
+    /// Emits one `Set-Cookie` header per change recorded in the cookie jar
     #[cfg(feature = "cookie")]
-    fn cookie_jar(&self) -> &cookie::CookieJar;
+    fn finalize_cookies(&mut self) -> Result<()>;
 
+    /// Adds the cookie to the jar and emits the pending changes of the jar, returning whether
+    /// `Set-Cookie` headers were present before
     #[cfg(feature = "cookie")]
     fn set_cookie(&mut self, cookie: cookie::Cookie<'_>) -> Result<bool>;
+
+    /// Emits a `Set-Cookie` deleting the cookie of the name, with an empty value, `Max-Age=0`
+    /// and an `Expires` in the past, and drops it from the jar.
+    ///
+    /// Browsers only delete a cookie whose `Path` and `Domain` match, taken from the
+    /// template, or else the cookie in the jar, defaulting to `Path=/`.
+    #[cfg(feature = "cookie")]
+    fn remove_cookie(&mut self, name: &str, template: Option<&cookie::Cookie<'_>>) -> Result<()>;
+
+    /// Like [`set_cookie`](Self::set_cookie), signing the value with the key so it can be
+    /// read but not altered, see
+    /// [`RequestExt::signed_cookie`](crate::RequestExt::signed_cookie)
+    #[cfg(feature = "cookie-secure")]
+    fn set_signed_cookie(&mut self, key: &cookie::Key, cookie: cookie::Cookie<'_>) -> Result<bool>;
+
+    /// Like [`set_cookie`](Self::set_cookie), encrypting the value with the key so it can be
+    /// neither read nor altered, see
+    /// [`RequestExt::private_cookie`](crate::RequestExt::private_cookie)
+    #[cfg(feature = "cookie-secure")]
+    fn set_private_cookie(&mut self, key: &cookie::Key, cookie: cookie::Cookie<'_>)
+        -> Result<bool>;
+
+    /// Sets a fresh CSRF token in an `HttpOnly` cookie signed with `key`, returning the
+    /// token to embed in forms or pages, see
+    /// [`RequestExt::verify_csrf`](crate::RequestExt::verify_csrf)
+    #[cfg(feature = "csrf")]
+    fn set_csrf_token(&mut self, key: &[u8]) -> Result<String>;
 }
 
+#[async_trait]
 impl ResponseExt for Response<Body> {
-    #[cfg(feature = "cookie")]
-    fn cookie_jar(&self) -> &cookie::CookieJar {
-        todo!()
+    fn canonicalize_headers(mut self) -> Self {
+        let headers = std::mem::take(self.headers_mut());
+
+        let mut names = headers.keys().collect::<Vec<_>>();
+        names.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+
+        for name in names {
+            for value in headers.get_all(name) {
+                self.headers_mut().append(name.clone(), value.clone());
+            }
+        }
+
+        self
     }
 
-    #[cfg(feature = "cookie")]
-    fn set_cookie(&mut self, cookie: cookie::Cookie<'_>) -> Result<bool> {
-        HeaderValue::from_str(&cookie.encoded().to_string())
-            .map(|v| self.headers_mut().append(header::SET_COOKIE, v))
-            .map_err(Into::into)
+    fn tee_body<F>(self, mut f: F) -> Self
+    where
+        F: FnMut(&bytes::Bytes) + Send + 'static,
+    {
+        use futures_util::TryStreamExt;
+
+        self.map(|body| Body::wrap_stream(body.inspect_ok(move |chunk| f(chunk))))
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    fn strict_transport_security(
+        mut self,
+        max_age: Duration,
+        include_subdomains: bool,
+        preload: bool,
+    ) -> Self {
+        let mut value = format!("max-age={}", max_age.as_secs());
+        if include_subdomains {
+            value.push_str("; includeSubDomains");
+        }
+        if preload {
+            value.push_str("; preload");
+        }
+        self.headers_mut().insert(
+            header::STRICT_TRANSPORT_SECURITY,
+            HeaderValue::from_str(&value).unwrap(),
+        );
+        self
+    }
 
-    #[test]
-    fn response() -> Result<()> {
-        let res = Response::text("hello world");
-        assert_eq!(
-            res.headers().get(header::CONTENT_TYPE),
-            Some(&HeaderValue::from_static(mime::TEXT_PLAIN.as_ref()))
+    fn content_security_policy(mut self, policy: &crate::ContentSecurityPolicy) -> Self {
+        self.headers_mut().insert(
+            header::CONTENT_SECURITY_POLICY,
+            HeaderValue::from_str(&policy.to_string()).unwrap(),
+        );
+        self
+    }
+
+    fn x_frame_options(mut self, mode: crate::FrameOptions) -> Self {
+        self.headers_mut().insert(
+            header::X_FRAME_OPTIONS,
+            HeaderValue::from_static(mode.as_str()),
+        );
+        self
+    }
+
+    fn referrer_policy(mut self, policy: crate::ReferrerPolicy) -> Self {
+        self.headers_mut().insert(
+            header::REFERRER_POLICY,
+            HeaderValue::from_static(policy.as_str()),
+        );
+        self
+    }
+
+    fn cors(mut self, cors: &crate::Cors, origin: Option<&str>) -> Self {
+        let allowed = cors
+            .allow_origin(origin)
+            .and_then(|o| HeaderValue::from_str(o).ok());
+        let headers = self.headers_mut();
+
+        if allowed.as_ref().is_none_or(|o| o != "*") {
+            headers.append(header::VARY, HeaderValue::from_static("origin"));
+        }
+        if let Some(allowed) = allowed {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allowed);
+            if cors.credentials() {
+                headers.insert(
+                    header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                    HeaderValue::from_static("true"),
+                );
+            }
+        }
+        self
+    }
+
+    fn chunked_by(self, size: usize) -> Self {
+        use futures_util::{stream, StreamExt};
+
+        assert!(size > 0, "chunk size must not be zero");
+
+        self.map(|body| {
+            let chunks = stream::once(crate::body::to_bytes(body)).flat_map(move |body| {
+                let chunks = match body {
+                    Ok(body) => (0..body.len())
+                        .step_by(size)
+                        .map(|i| Ok(body.slice(i..body.len().min(i + size))))
+                        .collect(),
+                    Err(e) => vec![Err(e)],
+                };
+                stream::iter(chunks)
+            });
+            Body::wrap_stream(chunks)
+        })
+    }
+
+    #[cfg(feature = "compress")]
+    fn compress(self, accept_encoding: &str) -> Self {
+        self.compress_with(accept_encoding, &crate::Compression::default())
+    }
+
+    #[cfg(feature = "compress")]
+    fn compress_with(mut self, accept_encoding: &str, config: &crate::Compression) -> Self {
+        use crate::{encode, negotiation::negotiate_encoding, Encoding};
+        use hyper::body::HttpBody;
+
+        let status = self.status();
+        let headers = self.headers();
+        let no_transform = headers
+            .get_all(header::CACHE_CONTROL)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .any(|d| d.trim().eq_ignore_ascii_case("no-transform"));
+        if no_transform
+            || status.is_informational()
+            || status == StatusCode::NO_CONTENT
+            || status == StatusCode::NOT_MODIFIED
+            || status == StatusCode::PARTIAL_CONTENT
+            || headers.contains_key(header::CONTENT_ENCODING)
+            || headers.contains_key(header::CONTENT_RANGE)
+        {
+            return self;
+        }
+
+        let mime = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok()?.parse::<mime::Mime>().ok());
+        if !config.applies(mime.as_ref(), self.body().size_hint().exact()) {
+            return self;
+        }
+
+        self.headers_mut()
+            .append(header::VARY, HeaderValue::from_static("accept-encoding"));
+
+        let offered = [Encoding::Brotli, Encoding::Gzip, Encoding::Deflate];
+        let encoding = negotiate_encoding(Some(accept_encoding), &offered);
+        let encoder = match encode::Encoder::new(encoding) {
+            Some(encoder) => encoder,
+            None => return self,
+        };
+
+        let headers = self.headers_mut();
+        headers.remove(header::CONTENT_LENGTH);
+        headers.insert(
+            header::CONTENT_ENCODING,
+            HeaderValue::from_static(encoding.as_str()),
+        );
+        if let Some(etag) = headers.get_mut(header::ETAG) {
+            if !etag.as_bytes().starts_with(b"W/") {
+                let weak = [&b"W/"[..], etag.as_bytes()].concat();
+                if let Ok(weak) = HeaderValue::from_bytes(&weak) {
+                    *etag = weak;
+                }
+            }
+        }
+
+        self.map(|body| Body::wrap_stream(encode::encode_stream(body, encoder)))
+    }
+
+    #[cfg(feature = "http1")]
+    fn status_reason(mut self, code: StatusCode, reason: &str) -> Result<Self> {
+        use std::convert::TryFrom;
+
+        *self.status_mut() = code;
+        if self.version() <= crate::Version::HTTP_11 {
+            let reason = crate::ext::ReasonPhrase::try_from(reason.as_bytes())?;
+            self.extensions_mut().insert(reason);
+        }
+        Ok(self)
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        let value = self
+            .headers()
+            .get(header::RETRY_AFTER)?
+            .to_str()
+            .ok()?
+            .trim();
+        if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) {
+            return value.parse().ok().map(Duration::from_secs);
+        }
+
+        let date = httpdate::parse_http_date(value).ok()?;
+        Some(
+            date.duration_since(SystemTime::now())
+                .unwrap_or(Duration::ZERO),
+        )
+    }
+
+    fn server_timing(mut self, metrics: &[(&str, Duration, Option<&str>)]) -> Result<Self> {
+        let mut value = String::new();
+        for (i, (name, duration, desc)) in metrics.iter().enumerate() {
+            let token = !name.is_empty()
+                && name
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b));
+            anyhow::ensure!(token, "invalid server timing metric `{}`", name);
+
+            if i > 0 {
+                value.push_str(", ");
+            }
+            let millis = duration.as_micros() as f64 / 1000.0;
+            value.push_str(&format!("{};dur={}", name, millis));
+            if let Some(desc) = desc {
+                let desc = desc.replace('\\', "\\\\").replace('"', "\\\"");
+                value.push_str(&format!(";desc=\"{}\"", desc));
+            }
+        }
+
+        self.headers_mut()
+            .append("server-timing", HeaderValue::from_str(&value)?);
+        Ok(self)
+    }
+
+    fn link_pagination(
+        mut self,
+        first: Option<&str>,
+        prev: Option<&str>,
+        next: Option<&str>,
+        last: Option<&str>,
+    ) -> Result<Self> {
+        let mut links = Vec::new();
+        for (rel, url) in [
+            ("first", first),
+            ("prev", prev),
+            ("next", next),
+            ("last", last),
+        ] {
+            if let Some(url) = url {
+                links.push(link(url, &format!("rel=\"{}\"", rel))?);
+            }
+        }
+
+        if !links.is_empty() {
+            self.headers_mut()
+                .append(header::LINK, HeaderValue::from_str(&links.join(", "))?);
+        }
+        Ok(self)
+    }
+
+    fn preload_links(mut self, links: &[(&str, &str)]) -> Result<Self> {
+        for (url, dest) in links {
+            anyhow::ensure!(
+                !dest.is_empty() && dest.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-'),
+                "invalid preload destination `{}`",
+                dest
+            );
+            let value = link(url, &format!("rel=preload; as={}", dest))?;
+            self.headers_mut()
+                .append(header::LINK, HeaderValue::from_str(&value)?);
+        }
+        Ok(self)
+    }
+
+    #[cfg(feature = "cookie")]
+    fn cookie_jar(&mut self) -> &mut cookie::CookieJar {
+        if self.extensions().get::<cookie::CookieJar>().is_none() {
+            let mut jar = cookie::CookieJar::new();
+            self.headers()
+                .get_all(header::SET_COOKIE)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .filter_map(|v| cookie::Cookie::parse_encoded(v.to_owned()).ok())
+                .filter(|c| !c.max_age().is_some_and(|age| age.is_zero()))
+                .for_each(|c| jar.add_original(c));
+            self.extensions_mut().insert(jar);
+        }
+        self.extensions_mut().get_mut().unwrap()
+    }
+
+    #[cfg(feature = "cookie")]
+    fn finalize_cookies(&mut self) -> Result<()> {
+        let jar = match self.extensions_mut().get_mut::<cookie::CookieJar>() {
+            Some(jar) => jar,
+            None => return Ok(()),
+        };
+
+        let delta = jar.delta().cloned().collect::<Vec<_>>();
+
+        // Settle the jar so the emitted changes are not sent twice
+        let mut settled = cookie::CookieJar::new();
+        jar.iter().cloned().for_each(|c| settled.add_original(c));
+        *jar = settled;
+
+        for cookie in delta {
+            let value = HeaderValue::from_str(&cookie.encoded().to_string())?;
+            self.headers_mut().append(header::SET_COOKIE, value);
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "cookie")]
+    fn set_cookie(&mut self, cookie: cookie::Cookie<'_>) -> Result<bool> {
+        HeaderValue::from_str(&cookie.encoded().to_string())?;
+
+        let existed = self.headers().contains_key(header::SET_COOKIE);
+        self.cookie_jar().add(cookie.into_owned());
+        self.finalize_cookies()?;
+        Ok(existed)
+    }
+
+    #[cfg(feature = "cookie")]
+    fn remove_cookie(&mut self, name: &str, template: Option<&cookie::Cookie<'_>>) -> Result<()> {
+        let jar = self.cookie_jar();
+        let (path, domain) = match template.or_else(|| jar.get(name)) {
+            Some(c) => (
+                c.path().map(ToOwned::to_owned),
+                c.domain().map(ToOwned::to_owned),
+            ),
+            None => (None, None),
+        };
+
+        let mut removal = cookie::Cookie::named(name.to_owned());
+        removal.set_path(path.unwrap_or_else(|| "/".to_owned()));
+        if let Some(domain) = domain {
+            removal.set_domain(domain);
+        }
+        HeaderValue::from_str(&removal.encoded().to_string())?;
+
+        // Only removing an original cookie records a removal to emit
+        jar.add_original(removal.clone());
+        jar.remove(removal);
+        self.finalize_cookies()
+    }
+
+    #[cfg(feature = "cookie-secure")]
+    fn set_signed_cookie(&mut self, key: &cookie::Key, cookie: cookie::Cookie<'_>) -> Result<bool> {
+        HeaderValue::from_str(&cookie.encoded().to_string())?;
+
+        let existed = self.headers().contains_key(header::SET_COOKIE);
+        self.cookie_jar().signed_mut(key).add(cookie.into_owned());
+        self.finalize_cookies()?;
+        Ok(existed)
+    }
+
+    #[cfg(feature = "cookie-secure")]
+    fn set_private_cookie(
+        &mut self,
+        key: &cookie::Key,
+        cookie: cookie::Cookie<'_>,
+    ) -> Result<bool> {
+        HeaderValue::from_str(&cookie.encoded().to_string())?;
+
+        let existed = self.headers().contains_key(header::SET_COOKIE);
+        self.cookie_jar().private_mut(key).add(cookie.into_owned());
+        self.finalize_cookies()?;
+        Ok(existed)
+    }
+
+    #[cfg(feature = "csrf")]
+    fn set_csrf_token(&mut self, key: &[u8]) -> Result<String> {
+        let token = crate::csrf::token()?;
+        self.set_cookie(
+            cookie::Cookie::build(crate::csrf::COOKIE, crate::csrf::sign(key, &token))
+                .path("/")
+                .http_only(true)
+                .same_site(cookie::SameSite::Lax)
+                .finish(),
+        )?;
+        Ok(token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "cookie")]
+    #[test]
+    fn finalize_cookies() -> Result<()> {
+        let mut res = Response::text("");
+        res.cookie_jar().add(cookie::Cookie::new("a", "1"));
+        res.cookie_jar().add(cookie::Cookie::new("b", "2"));
+        res.cookie_jar().remove(cookie::Cookie::named("a"));
+        res.cookie_jar().add_original(cookie::Cookie::new("c", "3"));
+        res.cookie_jar().remove(cookie::Cookie::named("c"));
+        res.finalize_cookies()?;
+
+        let mut values = res
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .map(|v| v.to_str().unwrap())
+            .collect::<Vec<_>>();
+        values.sort_unstable();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0], "b=2");
+        assert!(values[1].starts_with("c=; Max-Age=0"));
+
+        res.finalize_cookies()?;
+        assert_eq!(res.headers().get_all(header::SET_COOKIE).iter().count(), 2);
+        assert_eq!(res.cookie_jar().get("b").map(|c| c.value()), Some("2"));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "cookie")]
+    #[test]
+    fn set_cookie() -> Result<()> {
+        let mut res = Response::text("");
+        res.headers_mut().append(
+            header::SET_COOKIE,
+            HeaderValue::from_static("theme=dark; Path=/"),
+        );
+        res.headers_mut().append(
+            header::SET_COOKIE,
+            HeaderValue::from_static("old=; Max-Age=0"),
+        );
+
+        assert!(res.set_cookie(cookie::Cookie::new("a", "1"))?);
+        assert!(res.set_cookie(cookie::Cookie::new("b", "2 3"))?);
+        let invalid = cookie::Cookie::build("c", "4").path("/\n").finish();
+        assert!(res.set_cookie(invalid).is_err());
+
+        let jar = res.cookie_jar();
+        assert_eq!(jar.get("a").map(|c| c.value()), Some("1"));
+        assert_eq!(jar.get("b").map(|c| c.value()), Some("2 3"));
+        assert_eq!(jar.get("theme").map(|c| c.value()), Some("dark"));
+        assert!(jar.get("old").is_none());
+
+        let values = res
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .map(|v| v.to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            ["theme=dark; Path=/", "old=; Max-Age=0", "a=1", "b=2%203"]
+        );
+
+        Ok(())
+    }
+
+    #[cfg(feature = "cookie")]
+    #[test]
+    fn remove_cookie() -> Result<()> {
+        let set_cookies = |res: &Response<Body>| {
+            res.headers()
+                .get_all(header::SET_COOKIE)
+                .iter()
+                .map(|v| v.to_str().unwrap().to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        let mut res = Response::text("");
+        res.remove_cookie("session", None)?;
+        let values = set_cookies(&res);
+        assert_eq!(values.len(), 1);
+        assert!(values[0].starts_with("session=; Path=/; Max-Age=0; Expires="));
+        assert!(res.cookie_jar().get("session").is_none());
+
+        let mut res = Response::text("");
+        res.set_cookie(
+            cookie::Cookie::build("session", "abc")
+                .path("/app")
+                .domain("example.com")
+                .finish(),
+        )?;
+        res.remove_cookie("session", None)?;
+        let values = set_cookies(&res);
+        assert_eq!(values.len(), 2);
+        assert!(values[1].starts_with("session=; Path=/app; Domain=example.com; Max-Age=0"));
+        assert!(res.cookie_jar().get("session").is_none());
+
+        let mut res = Response::text("");
+        let template = cookie::Cookie::build("theme", "").path("/docs").finish();
+        res.remove_cookie("theme", Some(&template))?;
+        assert!(set_cookies(&res)[0].starts_with("theme=; Path=/docs; Max-Age=0"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn nested_result() {
+        use crate::{BoxHandler, Request, Router};
+
+        let app = Router::new().get(
+            "/:id",
+            BoxHandler::new(|req: Request<Body>| async move {
+                let id = req.uri().path().trim_start_matches('/').to_owned();
+                let result: Result<Result<Response<Body>>> = match id.as_str() {
+                    "outer" => Err(Error::Unauthorized.into()),
+                    "inner" => Ok(Err(Error::NotAcceptable.into())),
+                    _ => Ok(Ok(Response::text(id))),
+                };
+                flatten_response(result)
+            }),
+        );
+
+        for (uri, status) in [
+            ("/ok", StatusCode::OK),
+            ("/outer", StatusCode::UNAUTHORIZED),
+            ("/inner", StatusCode::NOT_ACCEPTABLE),
+        ] {
+            let res = app
+                .dispatch(Request::get(uri).body(Body::empty()).unwrap())
+                .await;
+            assert_eq!(res.status(), status);
+        }
+
+        let nested: Result<Result<Response<Body>>> = Ok(Err(anyhow::anyhow!("oops")));
+        assert_eq!(
+            nested.into_response().status(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+
+    #[test]
+    fn response() -> Result<()> {
+        let res = Response::text("hello world");
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE),
+            Some(&HeaderValue::from_static(mime::TEXT_PLAIN.as_ref()))
         );
 
         let res = Response::html("hello world");
@@ -98,4 +1490,910 @@ mod tests {
 
         Ok(())
     }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn json_sparse() -> Result<()> {
+        let user = serde_json::json!({ "id": 1, "name": "ada", "email": "ada@example.com" });
+
+        let res = Response::json_sparse(&user, Some(&["id", "name", "missing"]))?;
+        assert_eq!(
+            crate::body::to_bytes(res.into_body()).await?,
+            r#"{"id":1,"name":"ada"}"#
+        );
+
+        let res = Response::json_sparse([&user, &user], Some(&["email"]))?;
+        assert_eq!(
+            crate::body::to_bytes(res.into_body()).await?,
+            r#"[{"email":"ada@example.com"},{"email":"ada@example.com"}]"#
+        );
+
+        let res = Response::json_sparse(&user, None)?;
+        let body = crate::body::to_bytes(res.into_body()).await?;
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body)?, user);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn auto_serialize() -> Result<()> {
+        let req = |accept: &str| {
+            crate::Request::builder()
+                .header(header::ACCEPT, accept)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let res = Response::auto_serialize(&req("text/html, application/json;q=0.9"), [1, 2])?;
+        assert_eq!(
+            res.headers()[header::CONTENT_TYPE],
+            mime::APPLICATION_JSON.as_ref()
+        );
+
+        let res = Response::auto_serialize(&req("*/*"), [1, 2])?;
+        assert_eq!(
+            res.headers()[header::CONTENT_TYPE],
+            mime::APPLICATION_JSON.as_ref()
+        );
+
+        let err =
+            Response::auto_serialize(&req("text/html, application/json;q=0"), [1, 2]).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&Error::NotAcceptable));
+        assert_eq!(err.into_response().status(), StatusCode::NOT_ACCEPTABLE);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn auto_serialize_msgpack() -> Result<()> {
+        let req = crate::Request::builder()
+            .header(
+                header::ACCEPT,
+                "application/msgpack, application/json;q=0.5",
+            )
+            .body(Body::empty())?;
+
+        let res = Response::auto_serialize(&req, [1, 2])?;
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "application/msgpack");
+
+        Ok(())
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn auto_serialize_cbor() -> Result<()> {
+        let req = crate::Request::builder()
+            .header(header::ACCEPT, "application/cbor")
+            .body(Body::empty())?;
+
+        let res = Response::auto_serialize(&req, [1, 2])?;
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "application/cbor");
+
+        Ok(())
+    }
+
+    #[test]
+    fn redirect_to_https() -> Result<()> {
+        let uri = "/login?next=%2F".parse()?;
+
+        let res = Response::redirect_to_https("example.com:80", &uri)?;
+        assert_eq!(res.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(
+            res.headers()[header::LOCATION],
+            "https://example.com/login?next=%2F"
+        );
+
+        let res = Response::redirect_to_https("[::1]:8080", &"/".parse()?)?;
+        assert_eq!(res.headers()[header::LOCATION], "https://[::1]:8080/");
+
+        assert!(Response::redirect_to_https("evil.com/phish", &uri).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn unauthorized() -> Result<()> {
+        use crate::Challenge;
+
+        let res = Response::unauthorized(&Challenge::basic("admin area").to_string())?;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            res.headers()[header::WWW_AUTHENTICATE],
+            "Basic realm=\"admin area\", charset=\"UTF-8\""
+        );
+
+        let challenge = Challenge::bearer("api")
+            .error("invalid_token")
+            .error_description("The token \"abc\" expired");
+        let res = Response::unauthorized(&challenge.to_string())?;
+        assert_eq!(
+            res.headers()[header::WWW_AUTHENTICATE],
+            "Bearer realm=\"api\", error=\"invalid_token\", error_description=\"The token \\\"abc\\\" expired\""
+        );
+
+        assert_eq!(Challenge::new("Bearer").to_string(), "Bearer");
+        assert!(Response::unauthorized("Basic\n").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn retry_after() {
+        let with = |value: &str| {
+            let mut res = Response::default();
+            res.headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+            res.retry_after()
+        };
+
+        assert_eq!(with("120"), Some(Duration::from_secs(120)));
+        assert_eq!(with("Sun, 06 Nov 1994 08:49:37 GMT"), Some(Duration::ZERO));
+        let later = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(3600));
+        let delay = with(&later).unwrap();
+        assert!(delay > Duration::from_secs(3590) && delay <= Duration::from_secs(3600));
+
+        assert_eq!(with("-1"), None);
+        assert_eq!(with("soon"), None);
+        assert_eq!(Response::default().retry_after(), None);
+
+        let res = Response::service_unavailable(Some(RetryAfter::Delay(Duration::from_secs(5))));
+        assert_eq!(res.retry_after(), Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn server_timing() -> Result<()> {
+        let res = Response::text("").server_timing(&[
+            ("db", Duration::from_micros(53_250), Some("Query \"users\"")),
+            ("cache", Duration::from_millis(2), None),
+        ])?;
+        assert_eq!(
+            res.headers()["server-timing"],
+            "db;dur=53.25;desc=\"Query \\\"users\\\"\", cache;dur=2"
+        );
+
+        assert!(Response::text("")
+            .server_timing(&[("a b", Duration::ZERO, None)])
+            .is_err());
+        assert!(Response::text("")
+            .server_timing(&[("db", Duration::ZERO, Some("a\nb"))])
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn upgrade_required() -> Result<()> {
+        let res = Response::upgrade_required(&["websocket", "h2c"])?;
+        assert_eq!(res.status(), StatusCode::UPGRADE_REQUIRED);
+        assert_eq!(res.headers()[header::UPGRADE], "websocket, h2c");
+        assert_eq!(res.headers()[header::CONNECTION], "upgrade");
+
+        assert!(Response::upgrade_required(&["web\nsocket"]).is_err());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "fs")]
+    #[tokio::test]
+    async fn file_with_progress() -> Result<()> {
+        use std::sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        };
+
+        let path = std::env::temp_dir().join("hyperstone-file-with-progress.txt");
+        std::fs::write(&path, "hello world\n".repeat(4096))?;
+
+        let total = Arc::new(AtomicU64::new(0));
+        let res = Response::file_with_progress(&path, {
+            let total = total.clone();
+            move |sent| total.store(sent, Ordering::SeqCst)
+        })
+        .await?;
+
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "text/plain");
+        assert_eq!(res.headers()[header::CONTENT_LENGTH], "49152");
+
+        let body = crate::body::to_bytes(res.into_body()).await?;
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(body.len(), 49152);
+        assert_eq!(total.load(Ordering::SeqCst), 49152);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn multipart() -> Result<()> {
+        use futures_util::stream;
+
+        let parts = stream::iter(vec![
+            Ok(BodyPart::new("hello")
+                .header(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"))),
+            Ok(BodyPart::new("{}").header(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/json"),
+            )),
+        ]);
+        let res = Response::multipart("mixed", parts, Some("frontier"))?;
+        assert_eq!(
+            res.headers()[header::CONTENT_TYPE],
+            "multipart/mixed; boundary=\"frontier\""
+        );
+        assert_eq!(
+            crate::body::to_bytes(res.into_body()).await?,
+            "--frontier\r\ncontent-type: text/plain\r\n\r\nhello\r\n\
+             --frontier\r\ncontent-type: application/json\r\n\r\n{}\r\n\
+             --frontier--\r\n"
+        );
+
+        let res = Response::multipart("byteranges", stream::empty(), None)?;
+        let ct = res.headers()[header::CONTENT_TYPE].to_str()?.to_owned();
+        let boundary = ct
+            .strip_prefix("multipart/byteranges; boundary=\"")
+            .and_then(|b| b.strip_suffix('"'))
+            .unwrap();
+        assert_eq!(boundary.len(), 32);
+        assert_eq!(
+            crate::body::to_bytes(res.into_body()).await?,
+            format!("--{}--\r\n", boundary)
+        );
+
+        assert!(Response::multipart("mixed", stream::empty(), Some("a\"b")).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stream() -> Result<()> {
+        use crate::body::HttpBody;
+
+        let chunks = ["hello", " ", "world"].map(|s| Ok::<_, Error>(bytes::Bytes::from(s)));
+        let res = Response::stream(futures_util::stream::iter(chunks), "text/plain");
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "text/plain");
+        assert!(res.headers().get(header::CONTENT_LENGTH).is_none());
+        assert_eq!(res.body().size_hint().exact(), None);
+        assert_eq!(crate::body::to_bytes(res.into_body()).await?, "hello world");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn csv_stream() -> Result<()> {
+        use crate::body::HttpBody;
+
+        let rows = vec![
+            vec!["1", "plain"],
+            vec!["2", "a, b"],
+            vec!["3", "say \"hi\""],
+            vec!["4", "two\nlines"],
+        ];
+        let res = Response::csv_stream(
+            &["id", "note"],
+            futures_util::stream::iter(rows.into_iter().map(Ok::<_, Error>)),
+        );
+        assert_eq!(
+            res.headers()[header::CONTENT_TYPE],
+            "text/csv; charset=utf-8"
+        );
+        assert_eq!(res.body().size_hint().exact(), None);
+
+        let mut body = res.into_body();
+        let mut records = Vec::new();
+        while let Some(chunk) = body.data().await {
+            records.push(String::from_utf8(chunk?.to_vec())?);
+        }
+        assert_eq!(
+            records,
+            [
+                "id,note\r\n",
+                "1,plain\r\n",
+                "2,\"a, b\"\r\n",
+                "3,\"say \"\"hi\"\"\"\r\n",
+                "4,\"two\nlines\"\r\n",
+            ]
+        );
+
+        let res = Response::csv_stream(
+            &[],
+            futures_util::stream::iter(vec![Ok::<_, Error>(vec!["x".to_owned()])]),
+        );
+        assert_eq!(crate::body::to_bytes(res.into_body()).await?, "x\r\n");
+
+        Ok(())
+    }
+
+    #[cfg(feature = "http1")]
+    #[tokio::test]
+    async fn chunked() -> Result<()> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (mut client, server) = tokio::io::duplex(1024);
+        let service = crate::service::service_fn(|_| async {
+            let chunks = ["hello", " world"].map(|s| Ok::<_, Error>(bytes::Bytes::from(s)));
+            let res = Response::chunked(futures_util::stream::iter(chunks), "text/plain");
+            assert_eq!(res.headers()[header::TRANSFER_ENCODING], "chunked");
+            Ok::<_, std::convert::Infallible>(res)
+        });
+        let conn = tokio::spawn(crate::server::conn::Http::new().serve_connection(server, service));
+
+        client
+            .write_all(b"GET / HTTP/1.1\r\nhost: x\r\nconnection: close\r\n\r\n")
+            .await?;
+        let mut raw = String::new();
+        client.read_to_string(&mut raw).await?;
+        conn.await??;
+
+        let (head, body) = raw.split_once("\r\n\r\n").unwrap();
+        assert!(head.contains("transfer-encoding: chunked"));
+        assert!(!head.contains("content-length"));
+        assert_eq!(body, "5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n");
+
+        Ok(())
+    }
+
+    #[cfg(feature = "sse")]
+    #[tokio::test]
+    async fn event_stream() -> Result<()> {
+        use crate::sse::Event;
+
+        let events = [
+            Event::new().event("greeting").data("hello\nworld"),
+            Event::comment(""),
+        ]
+        .map(Ok::<_, Error>);
+        let res = Response::event_stream(futures_util::stream::iter(events));
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "text/event-stream");
+        assert_eq!(res.headers()[header::CACHE_CONTROL], "no-cache");
+        assert_eq!(
+            crate::body::to_bytes(res.into_body()).await?,
+            "event: greeting\ndata: hello\ndata: world\n\n:\n\n"
+        );
+
+        Ok(())
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn json_array_stream() -> Result<()> {
+        use futures_util::stream;
+
+        let items =
+            stream::iter([1, 2, 3].map(|id| Ok::<_, Error>(serde_json::json!({ "id": id }))));
+        let res = Response::json_array_stream(items);
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "application/json");
+        assert_eq!(
+            crate::body::to_bytes(res.into_body()).await?,
+            r#"[{"id":1},{"id":2},{"id":3}]"#
+        );
+
+        let res = Response::json_array_stream(stream::empty::<Result<u8>>());
+        assert_eq!(crate::body::to_bytes(res.into_body()).await?, "[]");
+
+        Ok(())
+    }
+
+    #[cfg(feature = "compress")]
+    #[tokio::test]
+    async fn compress() -> Result<()> {
+        use std::io::Read;
+
+        let text = "hello world\n".repeat(256);
+        let res = || {
+            let mut res = Response::text(text.clone());
+            res.headers_mut()
+                .insert(header::ETAG, HeaderValue::from_static("\"v1\""));
+            res
+        };
+
+        let gzipped = res().compress("gzip, br;q=0.5");
+        let headers = gzipped.headers();
+        assert_eq!(headers[header::CONTENT_ENCODING], "gzip");
+        assert_eq!(headers[header::VARY], "accept-encoding");
+        assert_eq!(headers[header::ETAG], "W/\"v1\"");
+        assert!(!headers.contains_key(header::CONTENT_LENGTH));
+        let body = crate::body::to_bytes(gzipped.into_body()).await?;
+        assert!(body.len() < text.len());
+        let mut decoded = String::new();
+        flate2::read::GzDecoder::new(&body[..]).read_to_string(&mut decoded)?;
+        assert_eq!(decoded, text);
+
+        let brotli = res().compress("gzip, deflate, br");
+        assert_eq!(brotli.headers()[header::CONTENT_ENCODING], "br");
+        let body = crate::body::to_bytes(brotli.into_body()).await?;
+        let mut decoded = String::new();
+        brotli::Decompressor::new(&body[..], 4096).read_to_string(&mut decoded)?;
+        assert_eq!(decoded, text);
+
+        let identity = res().compress("identity");
+        assert!(!identity.headers().contains_key(header::CONTENT_ENCODING));
+        assert_eq!(identity.headers()[header::VARY], "accept-encoding");
+        assert_eq!(identity.headers()[header::ETAG], "\"v1\"");
+
+        let small = Response::text("hello").compress("gzip");
+        assert!(!small.headers().contains_key(header::CONTENT_ENCODING));
+        assert!(!small.headers().contains_key(header::VARY));
+
+        let mut image = res();
+        image
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static("image/png"));
+        assert!(!image
+            .compress("gzip")
+            .headers()
+            .contains_key(header::CONTENT_ENCODING));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "compress")]
+    #[test]
+    fn compress_exceptions() {
+        use crate::Compression;
+
+        let text = "hello world\n".repeat(256);
+        let res = |ct: &'static str| {
+            let mut res = Response::text(text.clone());
+            res.headers_mut()
+                .insert(header::CONTENT_TYPE, HeaderValue::from_static(ct));
+            res
+        };
+        let encoding = |res: Response<Body>| {
+            res.headers()
+                .get(header::CONTENT_ENCODING)
+                .map(|v| v.to_str().unwrap().to_owned())
+        };
+
+        assert_eq!(encoding(res("image/png").compress("gzip")), None);
+        assert_eq!(encoding(res("video/mp4").compress("gzip")), None);
+        assert_eq!(encoding(res("application/gzip").compress("gzip")), None);
+        assert_eq!(
+            encoding(res("image/svg+xml").compress("gzip")).as_deref(),
+            Some("gzip")
+        );
+
+        let mut no_transform = res("text/plain");
+        no_transform.headers_mut().insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("public, No-Transform"),
+        );
+        let no_transform = no_transform.compress("gzip");
+        assert!(!no_transform.headers().contains_key(header::VARY));
+        assert_eq!(encoding(no_transform), None);
+
+        let config = Compression::new()
+            .skip("text/*".parse().unwrap())
+            .min_size(64);
+        assert_eq!(
+            encoding(res("text/plain").compress_with("gzip", &config)),
+            None
+        );
+        assert_eq!(
+            encoding(res("application/json").compress_with("gzip", &config)).as_deref(),
+            Some("gzip")
+        );
+
+        let config = Compression::new().skip_none();
+        assert_eq!(
+            encoding(res("image/png").compress_with("gzip", &config)).as_deref(),
+            Some("gzip")
+        );
+        let config = Compression::new().min_size(1 << 20);
+        assert_eq!(
+            encoding(res("text/plain").compress_with("gzip", &config)),
+            None
+        );
+    }
+
+    #[cfg(all(feature = "json", feature = "compress"))]
+    #[tokio::test]
+    async fn json_array_stream_compressed() -> Result<()> {
+        use futures_util::stream;
+        use std::io::Read;
+
+        let items = || stream::iter((0..1000).map(Ok::<_, Error>));
+        let req = |accept: &str| {
+            crate::Request::get("/")
+                .header(header::ACCEPT_ENCODING, accept)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let res = Response::json_array_stream_compressed(&req("gzip, br"), items());
+        assert_eq!(res.headers()[header::CONTENT_ENCODING], "gzip");
+        assert_eq!(res.headers()[header::VARY], "accept-encoding");
+        let body = crate::body::to_bytes(res.into_body()).await?;
+        let mut json = String::new();
+        flate2::read::GzDecoder::new(&body[..]).read_to_string(&mut json)?;
+        let values = serde_json::from_str::<Vec<u32>>(&json)?;
+        assert_eq!(values, (0..1000).collect::<Vec<_>>());
+
+        let res = Response::json_array_stream_compressed(&req("br"), items());
+        assert!(res.headers().get(header::CONTENT_ENCODING).is_none());
+        let body = crate::body::to_bytes(res.into_body()).await?;
+        assert_eq!(serde_json::from_slice::<Vec<u32>>(&body)?.len(), 1000);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn from_reader() -> Result<()> {
+        let data = "hello world\n".repeat(1024);
+        let reader = futures_util::io::Cursor::new(data.clone().into_bytes());
+
+        let res = Response::from_reader(reader, mime::TEXT_PLAIN.as_ref());
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "text/plain");
+        assert_eq!(crate::body::to_bytes(res.into_body()).await?, data);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn tee_body() -> Result<()> {
+        use std::sync::{Arc, Mutex};
+
+        let chunks = ["hello", " ", "world"];
+        let body = Body::wrap_stream(futures_util::stream::iter(
+            chunks.map(Ok::<_, std::io::Error>),
+        ));
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let res = Response::text(body).tee_body({
+            let seen = seen.clone();
+            move |chunk| seen.lock().unwrap().push(chunk.clone())
+        });
+
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "text/plain");
+        assert_eq!(crate::body::to_bytes(res.into_body()).await?, "hello world");
+        assert_eq!(*seen.lock().unwrap(), chunks);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn continue_or_reject() {
+        use crate::{BoxHandler, Request, Router};
+
+        let app = Router::new().put(
+            "/upload",
+            BoxHandler::new(|req: Request<Body>| async move {
+                let small = req.content_length().is_some_and(|n| n <= 1024);
+                Response::continue_or_reject(&req, small)?;
+                let body = crate::body::to_bytes(req.into_body()).await?;
+                Ok(Response::text(body))
+            }),
+        );
+
+        let put = |length: u64| {
+            Request::put("/upload")
+                .header(header::EXPECT, "100-Continue")
+                .header(header::CONTENT_LENGTH, length)
+                .body(Body::from("x".repeat(length as usize)))
+                .unwrap()
+        };
+
+        let res = app.dispatch(put(4096)).await;
+        assert_eq!(res.status(), StatusCode::EXPECTATION_FAILED);
+
+        let res = app.dispatch(put(16)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn partial_bytes() -> Result<()> {
+        let data = bytes::Bytes::from_static(b"hello world");
+
+        let res = Response::partial_bytes(data.clone(), 6..=10);
+        assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(res.headers()[header::CONTENT_RANGE], "bytes 6-10/11");
+        assert_eq!(res.headers()[header::CONTENT_LENGTH], "5");
+        assert_eq!(crate::body::to_bytes(res.into_body()).await?.len(), 5);
+
+        let res = Response::partial_bytes(data.clone(), 4..=100);
+        assert_eq!(res.headers()[header::CONTENT_RANGE], "bytes 4-10/11");
+        assert_eq!(crate::body::to_bytes(res.into_body()).await?, "o world");
+
+        let res = Response::partial_bytes(data, 11..=20);
+        assert_eq!(res.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(res.headers()[header::CONTENT_RANGE], "bytes */11");
+
+        Ok(())
+    }
+
+    #[test]
+    fn link_pagination() -> Result<()> {
+        let res = Response::text("").link_pagination(
+            None,
+            None,
+            Some("https://api.example.com/items?page=3"),
+            Some("https://api.example.com/items?page=9"),
+        )?;
+        assert_eq!(
+            res.headers()[header::LINK],
+            "<https://api.example.com/items?page=3>; rel=\"next\", \
+             <https://api.example.com/items?page=9>; rel=\"last\""
+        );
+
+        let res = Response::text("").link_pagination(None, None, None, None)?;
+        assert!(!res.headers().contains_key(header::LINK));
+
+        assert!(Response::text("")
+            .link_pagination(Some("/items?page=1>; rel=\"x"), None, None, None)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn status_constructors() -> Result<()> {
+        let res = Response::created("/users/42");
+        assert_eq!(res.status(), StatusCode::CREATED);
+        assert_eq!(res.headers()[header::LOCATION], "/users/42");
+
+        let res = Response::no_content();
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert!(res.headers().is_empty());
+        assert!(crate::body::to_bytes(res.into_body()).await?.is_empty());
+
+        assert_eq!(Response::accepted().status(), StatusCode::ACCEPTED);
+        assert_eq!(Response::bad_request().status(), StatusCode::BAD_REQUEST);
+        assert_eq!(Response::not_found().status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_with_status() -> Result<()> {
+        let res = Response::json_with_status(
+            serde_json::json!({ "error": "name is required" }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )?;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "application/json");
+
+        Ok(())
+    }
+
+    #[test]
+    fn preload_links() -> Result<()> {
+        let res = Response::html("")
+            .preload_links(&[("/app.css", "style"), ("/fonts/inter.woff2", "font")])?;
+        let links = res
+            .headers()
+            .get_all(header::LINK)
+            .iter()
+            .map(|v| v.to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            links,
+            [
+                "</app.css>; rel=preload; as=style",
+                "</fonts/inter.woff2>; rel=preload; as=font"
+            ]
+        );
+
+        assert!(Response::html("")
+            .preload_links(&[("/app.css", "style; rel=x")])
+            .is_err());
+        assert!(Response::html("")
+            .preload_links(&[("/a b.css", "style")])
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn download() {
+        let res = Response::download("a,b", "report 2021.csv", "text/csv");
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "text/csv");
+        assert_eq!(
+            res.headers()[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"report 2021.csv\""
+        );
+
+        let res = Response::download("", "résumé \"final\".pdf", "application/pdf");
+        assert_eq!(
+            res.headers()[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"r_sum_ \\\"final\\\".pdf\"; \
+             filename*=UTF-8''r%C3%A9sum%C3%A9%20%22final%22.pdf"
+        );
+    }
+
+    #[test]
+    fn cors() {
+        use crate::Cors;
+
+        let cors = Cors::new("*").allow_credentials(true);
+        let res = Response::text("").cors(&cors, Some("https://app.example.com"));
+        assert_eq!(
+            res.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://app.example.com"
+        );
+        assert_eq!(
+            res.headers()[header::ACCESS_CONTROL_ALLOW_CREDENTIALS],
+            "true"
+        );
+        assert_eq!(res.headers()[header::VARY], "origin");
+
+        let res = Response::text("").cors(&cors, None);
+        assert!(!res
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+
+        let res = Response::text("").cors(&Cors::new("*"), Some("https://a.example"));
+        assert_eq!(res.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        assert!(!res.headers().contains_key(header::VARY));
+
+        let cors = Cors::new("https://app.example.com");
+        let res = Response::text("").cors(&cors, Some("https://evil.example"));
+        assert!(!res
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+
+    #[test]
+    fn preflight() {
+        use crate::Cors;
+
+        let cors = Cors::new("https://app.example.com")
+            .allow_methods(&[Method::GET, Method::POST])
+            .allow_headers(&["Content-Type", "X-Requested-With"])
+            .max_age(Duration::from_secs(600))
+            .allow_credentials(true);
+
+        let res = Response::preflight(&cors, Some("https://app.example.com"));
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        let headers = res.headers();
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://app.example.com"
+        );
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_METHODS], "GET, POST");
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_HEADERS],
+            "content-type, x-requested-with"
+        );
+        assert_eq!(headers[header::ACCESS_CONTROL_MAX_AGE], "600");
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+
+        let res = Response::preflight(&cors, Some("https://evil.example"));
+        assert_eq!(res.headers().len(), 1);
+    }
+
+    #[test]
+    fn security_headers() {
+        use crate::{ContentSecurityPolicy, FrameOptions, ReferrerPolicy};
+
+        let policy = ContentSecurityPolicy::new()
+            .directive("default-src", &["'self'"])
+            .directive("img-src", &["'self'", "data:", "https://cdn.example.com"])
+            .directive("upgrade-insecure-requests", &[])
+            .directive("Default-Src", &["'none'"]);
+
+        let res = Response::text("")
+            .strict_transport_security(Duration::from_secs(31_536_000), true, true)
+            .content_security_policy(&policy)
+            .x_frame_options(FrameOptions::Deny)
+            .referrer_policy(ReferrerPolicy::StrictOriginWhenCrossOrigin);
+
+        assert_eq!(
+            res.headers()[header::STRICT_TRANSPORT_SECURITY],
+            "max-age=31536000; includeSubDomains; preload"
+        );
+        assert_eq!(
+            res.headers()[header::CONTENT_SECURITY_POLICY],
+            "default-src 'none'; img-src 'self' data: https://cdn.example.com; upgrade-insecure-requests"
+        );
+        assert_eq!(res.headers()[header::X_FRAME_OPTIONS], "DENY");
+        assert_eq!(
+            res.headers()[header::REFERRER_POLICY],
+            "strict-origin-when-cross-origin"
+        );
+
+        let res =
+            Response::text("").strict_transport_security(Duration::from_secs(60), false, false);
+        assert_eq!(
+            res.headers()[header::STRICT_TRANSPORT_SECURITY],
+            "max-age=60"
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn content_security_policy_injection() {
+        crate::ContentSecurityPolicy::new().directive("script-src", &["'self'; script-src *"]);
+    }
+
+    #[tokio::test]
+    async fn chunked_by() -> Result<()> {
+        use futures_util::StreamExt;
+
+        let res = Response::text("hello world").chunked_by(4);
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "text/plain");
+
+        let chunks = res
+            .into_body()
+            .map(|chunk| chunk.unwrap())
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(chunks, ["hell", "o wo", "rld"]);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "http1")]
+    #[test]
+    fn status_reason() -> Result<()> {
+        let res = Response::text("").status_reason(StatusCode::OK, "Alright")?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.extensions()
+                .get::<crate::ext::ReasonPhrase>()
+                .map(|r| r.as_bytes()),
+            Some(&b"Alright"[..])
+        );
+
+        let mut res = Response::text("");
+        *res.version_mut() = crate::Version::HTTP_2;
+        let res = res.status_reason(StatusCode::OK, "Alright")?;
+        assert!(res.extensions().get::<crate::ext::ReasonPhrase>().is_none());
+
+        assert!(Response::text("")
+            .status_reason(StatusCode::OK, "Al\nright")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn service_unavailable() {
+        let res = Response::service_unavailable(None);
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(res.headers().get(header::RETRY_AFTER).is_none());
+
+        let res = Response::service_unavailable(Some(RetryAfter::Delay(Duration::from_secs(120))));
+        assert_eq!(res.headers()[header::RETRY_AFTER], "120");
+
+        let res =
+            Response::service_unavailable(Some(RetryAfter::Delay(Duration::from_millis(1500))));
+        assert_eq!(res.headers()[header::RETRY_AFTER], "2");
+
+        let date = SystemTime::UNIX_EPOCH + Duration::from_secs(784_111_777);
+        let res = Response::service_unavailable(Some(RetryAfter::Date(date)));
+        assert_eq!(
+            res.headers()[header::RETRY_AFTER],
+            "Sun, 06 Nov 1994 08:49:37 GMT"
+        );
+    }
+
+    #[test]
+    fn canonicalize_headers() {
+        let mut res = Response::text("hello world");
+        res.headers_mut()
+            .insert(header::VARY, HeaderValue::from_static("origin"));
+        res.headers_mut()
+            .append(header::SET_COOKIE, HeaderValue::from_static("a=1"));
+        res.headers_mut()
+            .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        res.headers_mut()
+            .append(header::SET_COOKIE, HeaderValue::from_static("b=2"));
+
+        let res = res.canonicalize_headers();
+        assert_eq!(
+            res.headers()
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.to_str().unwrap()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("cache-control", "no-cache"),
+                ("content-type", "text/plain"),
+                ("set-cookie", "a=1"),
+                ("set-cookie", "b=2"),
+                ("vary", "origin"),
+            ]
+        );
+    }
 }
@@ -1,12 +1,201 @@
-use crate::Method;
+use crate::request::RawBody;
+use crate::{
+    anyhow::Result, header, header::HeaderValue, Body, BoxHandler, Error, IntoResponse, Method,
+    Middleware, Next, Request, RequestExt, Response, StatusCode, Uri, Version,
+};
+use bytes::{Bytes, BytesMut};
+use futures_util::StreamExt;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    error::Error as StdError,
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+use tower_service::Service;
+
+#[derive(Clone, Debug)]
+struct Route<T> {
+    method: Method,
+    path: String,
+    params: Vec<(String, ParamKind)>,
+    handler: T,
+    expects: Option<mime::Mime>,
+    name: Option<String>,
+    deprecation: Option<Option<SystemTime>>,
+    produces: Option<mime::Mime>,
+    min_version: Option<Version>,
+    /// The middleware of the scopes the route was added through, outermost first
+    layers: Vec<Layer>,
+    /// The response transforms of the scopes the route was added through, innermost first
+    wraps: Vec<Wrap>,
+    /// The [`capture_body`](Router::capture_body) limit of the innermost scope setting one
+    capture_body: Option<usize>,
+    /// The [`body_limit`](Router::body_limit) of the innermost scope setting one
+    body_limit: Option<usize>,
+    /// Whether the middleware of the router holding the route applies to it, which stops
+    /// once a scope along the way does not [`inherit`](Router::inherit)
+    inherits: bool,
+}
 
 #[derive(Debug)]
 pub struct Router<T> {
     inherit: bool,
     path: String,
     name: Option<String>,
-    tree: path_tree::PathTree<T>,
-    routes: Option<Vec<(Method, String, T)>>,
+    slots: Vec<(String, Vec<usize>)>,
+    ranked: Vec<usize>,
+    /// The position of each slot in `ranked`
+    positions: Vec<usize>,
+    /// The slots of the patterns starting with a static segment, by that segment
+    index: HashMap<String, Vec<usize>>,
+    /// The slots of the patterns starting with a parameter or catch-all
+    dynamic: Vec<usize>,
+    precedence: Precedence,
+    routes: Vec<Route<T>>,
+    wraps: Vec<Wrap>,
+    layers: Vec<Layer>,
+    capture_body: Option<usize>,
+    body_limit: Option<usize>,
+    access_log: Option<Logger>,
+    slow_requests: Option<(Duration, SlowHook)>,
+    merge_slashes: bool,
+    auto_head: bool,
+    auto_options: bool,
+    decode_path: Option<PathDecoding>,
+    fallback: Option<BoxHandler>,
+}
+
+/// The order in which [`Router`] tries the patterns matching a request path
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Precedence {
+    /// Segment by segment, static segments before parameters before catch-alls, so
+    /// `/users/new` beats `/users/:id` beats `/users/*rest`, whatever the registration order
+    Specificity,
+    /// The order the patterns were first registered in
+    Registration,
+}
+
+/// How [`Router::decode_path`] treats malformed percent-encodings in request paths
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathDecoding {
+    /// Rejects the request with `400`
+    Strict,
+    /// Keeps the malformed sequence as it is
+    Lenient,
+}
+
+/// A response transform registered by [`Router::wrap`]
+#[derive(Clone)]
+struct Wrap(Arc<dyn Fn(Response<Body>) -> Response<Body> + Send + Sync>);
+
+impl fmt::Debug for Wrap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Wrap").finish()
+    }
+}
+
+/// A middleware registered by [`Router::with`]
+#[derive(Clone)]
+struct Layer(Arc<dyn Middleware>);
+
+impl fmt::Debug for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Layer").finish()
+    }
+}
+
+/// An access log sink registered by [`Router::access_log`]
+#[derive(Clone)]
+struct Logger(Arc<dyn Fn(&AccessLog) + Send + Sync>);
+
+impl fmt::Debug for Logger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Logger").finish()
+    }
+}
+
+/// A slow-request callback registered by [`Router::slow_requests`]
+#[derive(Clone)]
+struct SlowHook(Arc<SlowFn>);
+
+type SlowFn = dyn Fn(&str, Duration) + Send + Sync;
+
+impl fmt::Debug for SlowHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SlowHook").finish()
+    }
+}
+
+/// An access log entry of a dispatched request
+#[derive(Clone, Debug)]
+pub struct AccessLog {
+    pub method: Method,
+    /// The pattern of the matched route, `None` when no route matched
+    pub pattern: Option<String>,
+    pub status: StatusCode,
+    /// The time until the response head was ready, not counting the body
+    pub duration: Duration,
+}
+
+/// The pattern of the matched route, in the request and response extensions
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchedPath {
+    path: String,
+    name: Option<String>,
+}
+
+impl MatchedPath {
+    pub fn as_str(&self) -> &str {
+        &self.path
+    }
+
+    /// The name of the matched route, if any
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
+/// A route as listed by [`Router::client_manifest`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ClientRoute {
+    /// The method, `*` for `any` routes
+    pub method: String,
+    /// The pattern, e.g. `/users/{id:int}`
+    pub path: String,
+    pub name: Option<String>,
+    pub params: Vec<ClientParam>,
+    /// The request media type the route [expects](Router::expects)
+    pub consumes: Option<String>,
+    /// The response media type the route [produces](Router::produces)
+    pub produces: Option<String>,
+    pub deprecated: bool,
+}
+
+/// A path parameter of a [`ClientRoute`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ClientParam {
+    pub name: String,
+    pub kind: ParamKind,
+}
+
+/// The result of [`Router::find`]
+#[derive(Debug, PartialEq, Eq)]
+pub enum Match<'a, T> {
+    /// The handler of the matched route, with the captured parameters as `(name, value)`
+    Found(&'a T, Vec<(&'a str, &'a str)>),
+    /// The path has routes, but not of the method, listing the methods they allow
+    MethodNotAllowed(Vec<Method>),
+    NotFound,
+}
+
+impl<T: Clone> Default for Router<T> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<T: Clone> Router<T> {
@@ -15,8 +204,24 @@ impl<T: Clone> Router<T> {
             inherit: false,
             path: "/".to_string(),
             name: None,
-            tree: path_tree::PathTree::new(),
-            routes: None,
+            slots: Vec::new(),
+            ranked: Vec::new(),
+            positions: Vec::new(),
+            index: HashMap::new(),
+            dynamic: Vec::new(),
+            precedence: Precedence::Specificity,
+            routes: Vec::new(),
+            wraps: Vec::new(),
+            layers: Vec::new(),
+            capture_body: None,
+            body_limit: None,
+            access_log: None,
+            slow_requests: None,
+            merge_slashes: false,
+            auto_head: true,
+            auto_options: true,
+            decode_path: None,
+            fallback: None,
         }
     }
 
@@ -30,24 +235,442 @@ impl<T: Clone> Router<T> {
         self
     }
 
+    /// Whether the middleware of the router this one is [`scope`](Self::scope)d into also
+    /// applies to this router's routes, `false` by default
     pub fn inherit(mut self, b: bool) -> Self {
         self.inherit = b;
         self
     }
 
-    pub fn with(mut self) -> Self {
+    /// Reads the body of matched requests up to `limit` bytes before the handler runs,
+    /// keeping a copy for [`RequestExt::raw_body`]. Larger bodies are rejected with `413`.
+    ///
+    /// The limit stays with the routes when the router is [scoped](Self::scope), over the
+    /// one of the outer routers.
+    pub fn capture_body(mut self, limit: usize) -> Self {
+        self.capture_body.replace(limit);
+        self
+    }
+
+    /// Rejects matched requests with bodies over `limit` bytes with `413` before the handler
+    /// runs, and makes it the [`BodyLimit`](crate::BodyLimit) of `json` and `form`.
+    ///
+    /// Bodies declaring a `Content-Length` are checked against it, others are read up front.
+    /// The limit stays with the routes when the router is [scoped](Self::scope), over the
+    /// one of the outer routers.
+    pub fn body_limit(mut self, limit: usize) -> Self {
+        self.body_limit.replace(limit);
+        self
+    }
+
+    /// Names the most recently registered route, for [`url_for`](Self::url_for)
+    pub fn named(mut self, name: &str) -> Self {
+        if let Some(route) = self.routes.last_mut() {
+            route.name.replace(name.to_owned());
+        }
+        self
+    }
+
+    /// Builds the path of the named route, percent-encoding the parameter values.
+    ///
+    /// Catch-all parameters keep their slashes as segment separators, other parameters have
+    /// them encoded as `%2F`. Extra parameters are ignored. Returns `None` when no route has
+    /// the name, or a parameter of its pattern is missing or not of its declared type, as the
+    /// path would not route back to the route.
+    pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Option<String> {
+        let route = self
+            .routes
+            .iter()
+            .find(|r| r.name.as_deref() == Some(name))?;
+        let catch_all = route
+            .path
+            .split('/')
+            .filter_map(|s| s.strip_prefix('*'))
+            .collect::<Vec<_>>();
+
+        fill(&route.path, |name| {
+            let (_, value) = params.iter().find(|(k, _)| *k == name)?;
+            let kind = route.params.iter().find(|(n, _)| n == name);
+            if kind.is_some_and(|(_, kind)| !kind.accepts(value)) {
+                return None;
+            }
+            Some(if catch_all.contains(&name) {
+                encode_path(value).into()
+            } else {
+                encode_segment(value).into()
+            })
+        })
+    }
+
+    /// Collapses runs of slashes in request paths before matching, so `/api//users` matches
+    /// `/api/users`. Encoded slashes, `%2F`, are left as they are.
+    pub fn merge_slashes(mut self, b: bool) -> Self {
+        self.merge_slashes = b;
+        self
+    }
+
+    /// Answers `HEAD` requests without a `HEAD` route with the `GET` route of the path,
+    /// dropping the body but keeping its headers. On by default.
+    pub fn auto_head(mut self, b: bool) -> Self {
+        self.auto_head = b;
+        self
+    }
+
+    /// Answers `OPTIONS` requests without an `OPTIONS` route with `204` and an `Allow` header
+    /// listing the methods of the path, or of all routes for `OPTIONS *`. On by default.
+    pub fn auto_options(mut self, b: bool) -> Self {
+        self.auto_options = b;
+        self
+    }
+
+    /// Percent-decodes request paths before matching, so `/caf%C3%A9` matches `/café`.
+    ///
+    /// Encoded slashes, `%2F`, stay encoded, so they cannot split a segment. A `%` not
+    /// followed by two hex digits, or escapes which do not decode to UTF-8, are handled as
+    /// the `mode` says.
+    pub fn decode_path(mut self, mode: PathDecoding) -> Self {
+        self.decode_path.replace(mode);
+        self
+    }
+
+    /// Runs the middleware around the handler of every route of this router, whenever
+    /// registered, and of the routes of scoped routers which [`inherit`](Self::inherit).
+    ///
+    /// Middleware run in registration order, outer routers' before inner ones', after the
+    /// router's own checks such as [`body_limit`](Self::body_limit), so they only see
+    /// requests reaching a handler.
+    pub fn with<M>(mut self, middleware: M) -> Self
+    where
+        M: Middleware,
+    {
+        self.layers.push(Layer(Arc::new(middleware)));
         self
     }
 
     fn on(mut self, method: Method, path: impl AsRef<str>, handler: T) -> Self {
-        self.routes.get_or_insert_with(Vec::new).push((
+        let path = join_paths(&self.path, path.as_ref());
+        self.push(Route {
             method,
-            join_paths(&self.path, path.as_ref()),
+            path,
+            params: Vec::new(),
             handler,
-        ));
+            expects: None,
+            name: None,
+            deprecation: None,
+            produces: None,
+            min_version: None,
+            layers: Vec::new(),
+            wraps: Vec::new(),
+            capture_body: None,
+            body_limit: None,
+            inherits: true,
+        });
+        self
+    }
+
+    /// Inserts the route, sharing a slot with the routes of an equivalent pattern
+    fn push(&mut self, mut route: Route<T>) {
+        let index = self.routes.len();
+        let (key, params) = compile(&route.path);
+        route.params = params;
+
+        match self.slots.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, indices)) => indices.push(index),
+            None => {
+                let slot = self.slots.len();
+                let specificity = rank(&key);
+                let at = self
+                    .ranked
+                    .partition_point(|i| rank(&self.slots[*i].0) <= specificity);
+                self.ranked.insert(at, slot);
+                self.positions.push(at);
+                for (position, slot) in self.ranked.iter().enumerate().skip(at) {
+                    self.positions[*slot] = position;
+                }
+
+                match first_segment(&key) {
+                    ":" | "*" => self.dynamic.push(slot),
+                    first => self.index.entry(first.to_owned()).or_default().push(slot),
+                }
+                self.slots.push((key, vec![index]));
+            }
+        }
+
+        self.routes.push(route);
+    }
+
+    /// Describes every route for generating typed clients: its method, pattern, name, typed
+    /// path parameters and the media types it expects and produces, in registration order.
+    ///
+    /// Handlers are type-erased, so the Rust types of bodies are not known; the media types
+    /// declared with [`expects`](Self::expects) and [`produces`](Self::produces) stand in
+    /// for them.
+    pub fn client_manifest(&self) -> Vec<ClientRoute> {
+        self.routes
+            .iter()
+            .map(|route| ClientRoute {
+                method: route.method.to_string(),
+                path: route.path.clone(),
+                name: route.name.clone(),
+                params: route
+                    .params
+                    .iter()
+                    .map(|(name, kind)| ClientParam {
+                        name: name.clone(),
+                        kind: *kind,
+                    })
+                    .collect(),
+                consumes: route.expects.as_ref().map(ToString::to_string),
+                produces: route.produces.as_ref().map(ToString::to_string),
+                deprecated: route.deprecation.is_some(),
+            })
+            .collect()
+    }
+
+    /// Lists the routes which can never match, as `METHOD /pattern`, because a route tried
+    /// before them accepts every path they do, e.g. `/users/{id:int}` registered after
+    /// `/users/:id`, or, with [`Precedence::Registration`], any route after `any("/*rest")`.
+    ///
+    /// Patterns are tried per the [`precedence`](Self::precedence). Within a pattern `any`
+    /// routes come after the method ones, so only a route of the same method and produced
+    /// type shadows; across patterns an earlier `any` route shadows too, as does one
+    /// declaring no produced type.
+    pub fn unreachable(&self) -> Vec<String> {
+        let order = self
+            .slot_order()
+            .flat_map(|slot| self.slots[slot].1.iter().map(move |i| (slot, *i)))
+            .collect::<Vec<_>>();
+        let any = any_method();
+
+        let shadowed = order
+            .iter()
+            .enumerate()
+            .filter(|(n, (slot, i))| {
+                let route = &self.routes[*i];
+                order[..*n].iter().any(|(earlier, j)| {
+                    let r = &self.routes[*j];
+                    let same = earlier == slot;
+                    (r.method == route.method || !same && r.method == any)
+                        && (r.produces == route.produces || !same && r.produces.is_none())
+                        && covers(
+                            (&self.slots[*earlier].0, &r.params),
+                            (&self.slots[*slot].0, &route.params),
+                        )
+                })
+            })
+            .map(|(_, (_, i))| *i)
+            .collect::<Vec<_>>();
+
+        self.slots
+            .iter()
+            .flat_map(|(_, indices)| indices)
+            .filter(|i| shadowed.contains(i))
+            .map(|i| format!("{} {}", self.routes[*i].method, self.routes[*i].path))
+            .collect()
+    }
+
+    /// Sets the order in which the patterns matching a path are tried, by
+    /// [`Specificity`](Precedence::Specificity) unless configured otherwise.
+    ///
+    /// When no route of a pattern takes the request, because of its method or typed
+    /// parameters, the next matching pattern is tried.
+    pub fn precedence(mut self, precedence: Precedence) -> Self {
+        self.precedence = precedence;
         self
     }
 
+    /// Lists the route patterns in the order they are tried, per the
+    /// [`precedence`](Self::precedence); routes of the same shape keep their registration
+    /// order.
+    pub fn match_order(&self) -> Vec<&str> {
+        self.slot_order()
+            .flat_map(|slot| self.slots[slot].1.iter())
+            .map(|i| self.routes[*i].path.as_str())
+            .collect()
+    }
+
+    /// The indices of the slots, in the order they are tried
+    fn slot_order(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.slots.len()).map(move |i| match self.precedence {
+            Precedence::Specificity => self.ranked[i],
+            Precedence::Registration => i,
+        })
+    }
+
+    /// The slots whose pattern matches the path, in the order they are tried, with the
+    /// captured values.
+    ///
+    /// Only the patterns starting with the path's first segment or with a parameter are
+    /// matched against it.
+    fn matches<'a>(&self, path: &'a str) -> Vec<(usize, Vec<&'a str>)> {
+        let mut slots = self
+            .index
+            .get(first_segment(path))
+            .into_iter()
+            .flatten()
+            .chain(&self.dynamic)
+            .copied()
+            .collect::<Vec<_>>();
+        match self.precedence {
+            Precedence::Specificity => slots.sort_unstable_by_key(|slot| self.positions[*slot]),
+            Precedence::Registration => slots.sort_unstable(),
+        }
+
+        slots
+            .into_iter()
+            .filter_map(|slot| Some((slot, match_key(&self.slots[slot].0, path)?)))
+            .collect()
+    }
+
+    /// Looks up the handler of the route matching the method and path, along with the
+    /// captured parameters as `(name, value)` pairs.
+    ///
+    /// Patterns are tried per the [`precedence`](Self::precedence). Method routes win over
+    /// `any` routes of the same pattern, and typed parameters must accept the captured
+    /// values. When only routes of other methods match the path, their
+    /// methods are returned instead, for a `405` with an `Allow` header.
+    pub fn find<'a>(&'a self, method: &Method, path: &'a str) -> Match<'a, T> {
+        let matches = self.matches(path);
+        let (route, values) = match self.lookup(&matches, method, None) {
+            Some(found) => found,
+            None => {
+                let allowed = self.allowed(&matches, path);
+                return if allowed.is_empty() {
+                    Match::NotFound
+                } else {
+                    Match::MethodNotAllowed(allowed)
+                };
+            }
+        };
+        let params = route
+            .params
+            .iter()
+            .zip(values)
+            .map(|((name, _), value)| (name.as_str(), value))
+            .collect();
+        Match::Found(&route.handler, params)
+    }
+
+    /// The methods of the routes of the [`matches`](Self::matches) of the path, or of all
+    /// routes for `*`, including `HEAD` answered by `GET` routes with
+    /// [`auto_head`](Self::auto_head) and `OPTIONS` with [`auto_options`](Self::auto_options)
+    fn allowed(&self, matches: &[(usize, Vec<&str>)], path: &str) -> Vec<Method> {
+        let mut methods = Vec::<Method>::new();
+        let mut allow = |method: &Method| {
+            if *method != any_method() && !methods.contains(method) {
+                methods.push(method.clone());
+            }
+        };
+
+        if path == "*" {
+            self.routes.iter().for_each(|route| allow(&route.method));
+        }
+        for (slot, values) in matches {
+            for route in self.slots[*slot].1.iter().map(|i| &self.routes[*i]) {
+                let accepts = route
+                    .params
+                    .iter()
+                    .zip(values)
+                    .all(|((_, kind), value)| kind.accepts(value));
+                if accepts {
+                    allow(&route.method);
+                }
+            }
+        }
+
+        if self.auto_head && methods.contains(&Method::GET) && !methods.contains(&Method::HEAD) {
+            methods.push(Method::HEAD);
+        }
+        if self.auto_options && !methods.is_empty() && !methods.contains(&Method::OPTIONS) {
+            methods.push(Method::OPTIONS);
+        }
+        methods
+    }
+
+    /// Returns the first route matching the method and path whose typed parameters accept the
+    /// captured values, falling back to the `GET` routes of `HEAD` requests with
+    /// [`auto_head`](Self::auto_head), then to `any` routes, before trying the next pattern.
+    ///
+    /// Among routes declaring what they [produce](Self::produces), the one the `Accept`
+    /// header prefers wins, otherwise the first route declaring nothing.
+    fn lookup<'a>(
+        &self,
+        matches: &[(usize, Vec<&'a str>)],
+        method: &Method,
+        accept: Option<&str>,
+    ) -> Option<(&Route<T>, Vec<&'a str>)> {
+        matches.iter().find_map(|(slot, values)| {
+            let route = self.select(*slot, values, method, accept)?;
+            Some((route, values.clone()))
+        })
+    }
+
+    /// [`lookup`](Self::lookup) within the routes of one slot
+    fn select(
+        &self,
+        slot: usize,
+        values: &[&str],
+        method: &Method,
+        accept: Option<&str>,
+    ) -> Option<&Route<T>> {
+        let routes = self.slots[slot].1.iter().map(|i| &self.routes[*i]);
+        let any = any_method();
+
+        let accepts = |r: &&Route<T>| {
+            r.params
+                .iter()
+                .zip(values)
+                .all(|((_, kind), value)| kind.accepts(value))
+        };
+
+        let select = |method: &Method| {
+            let candidates = routes
+                .clone()
+                .filter(|r| r.method == method)
+                .filter(accepts)
+                .collect::<Vec<_>>();
+            let offered = candidates
+                .iter()
+                .filter_map(|r| r.produces.clone())
+                .collect::<Vec<_>>();
+
+            crate::negotiation::negotiate(accept, &offered)
+                .and_then(|m| candidates.iter().find(|r| r.produces.as_ref() == Some(&m)))
+                .or_else(|| candidates.iter().find(|r| r.produces.is_none()))
+                .copied()
+        };
+
+        select(method)
+            .or_else(|| {
+                (self.auto_head && method == Method::HEAD)
+                    .then(|| select(&Method::GET))
+                    .flatten()
+            })
+            .or_else(|| select(&any))
+    }
+
+    /// [`lookup`](Self::lookup) with the captured values as typed [`Params`]
+    fn find_route(
+        &self,
+        matches: &[(usize, Vec<&str>)],
+        method: &Method,
+        accept: Option<&str>,
+    ) -> Option<(&Route<T>, Params)> {
+        let (route, values) = self.lookup(matches, method, accept)?;
+
+        Some((
+            route,
+            Params(
+                route
+                    .params
+                    .iter()
+                    .zip(values)
+                    .map(|((name, kind), value)| (name.clone(), value.to_owned(), *kind))
+                    .collect(),
+            ),
+        ))
+    }
+
     pub fn options(self, path: impl AsRef<str>, handler: T) -> Self {
         self.on(Method::OPTIONS, path, handler)
     }
@@ -85,59 +708,2220 @@ impl<T: Clone> Router<T> {
     }
 
     pub fn any(self, path: impl AsRef<str>, handler: T) -> Self {
-        self.on(Method::from_bytes(&[b'*']).unwrap(), path, handler)
+        self.on(any_method(), path, handler)
+    }
+
+    /// Requires requests to the most recently registered route to carry a matching
+    /// `Content-Type`, otherwise they are rejected with `415` before the handler runs.
+    ///
+    /// A `*` subtype, e.g. `text/*`, accepts any subtype of the type.
+    pub fn expects(mut self, content_type: mime::Mime) -> Self {
+        if let Some(route) = self.routes.last_mut() {
+            route.expects.replace(content_type);
+        }
+        self
+    }
+
+    /// Requires requests to the most recently registered route to use at least the HTTP
+    /// version, e.g. HTTP/1.1 for WebSocket upgrades, otherwise they are rejected with `505`.
+    pub fn min_version(mut self, version: Version) -> Self {
+        if let Some(route) = self.routes.last_mut() {
+            route.min_version.replace(version);
+        }
+        self
+    }
+
+    /// Declares the media type the most recently registered route produces, letting routes
+    /// of the same method and path be told apart by the `Accept` header.
+    ///
+    /// Requests accepting none of the declared types are rejected with `406`, unless a
+    /// route of the same method and path declares nothing.
+    pub fn produces(mut self, content_type: mime::Mime) -> Self {
+        if let Some(route) = self.routes.last_mut() {
+            route.produces.replace(content_type);
+        }
+        self
+    }
+
+    /// Marks the most recently registered route as deprecated, its responses carrying
+    /// `Deprecation: true` and, when given, the `Sunset` date after which it may go away
+    pub fn deprecated(mut self, sunset: Option<SystemTime>) -> Self {
+        if let Some(route) = self.routes.last_mut() {
+            route.deprecation.replace(sunset);
+        }
+        self
+    }
+
+    /// Adds the routes of the child router under this router's path.
+    ///
+    /// A named child prefixes the names of its routes with its own, so scoping `users` with
+    /// a `show` route into `api` makes it `api.users.show` once `api` is scoped in turn.
+    ///
+    /// The child's middleware, [`wrap`](Router::wrap) transforms,
+    /// [`body_limit`](Self::body_limit) and [`capture_body`](Self::capture_body) stay with
+    /// its routes. Its settings for requests before a route matches are dropped for this
+    /// router's: [`fallback`](Router::fallback), [`access_log`](Router::access_log),
+    /// [`slow_requests`](Router::slow_requests), [`decode_path`](Self::decode_path),
+    /// [`merge_slashes`](Self::merge_slashes), [`auto_head`](Self::auto_head),
+    /// [`auto_options`](Self::auto_options) and [`precedence`](Self::precedence).
+    pub fn scope(mut self, router: Self) -> Self {
+        for mut route in router.routes {
+            route.path = join_paths(&self.path, &route.path);
+            if route.inherits {
+                route.layers.splice(0..0, router.layers.iter().cloned());
+            }
+            route.inherits = route.inherits && router.inherit;
+            route.wraps.extend(router.wraps.iter().cloned());
+            route.capture_body = route.capture_body.or(router.capture_body);
+            route.body_limit = route.body_limit.or(router.body_limit);
+            if let (Some(scope), Some(name)) = (&router.name, &route.name) {
+                route.name = Some(format!("{}.{}", scope, name));
+            }
+            self.push(route);
+        }
+        self
+    }
+}
+
+/// A handler registered by the `#[route]` attribute, see [`Router::from_registered`]
+#[cfg(feature = "macros")]
+#[doc(hidden)]
+pub struct RegisteredRoute {
+    method: &'static str,
+    path: &'static str,
+    name: Option<&'static str>,
+    handler: fn() -> BoxHandler,
+}
+
+#[cfg(feature = "macros")]
+impl RegisteredRoute {
+    pub const fn new(
+        method: &'static str,
+        path: &'static str,
+        name: Option<&'static str>,
+        handler: fn() -> BoxHandler,
+    ) -> Self {
+        Self {
+            method,
+            path,
+            name,
+            handler,
+        }
+    }
+}
+
+#[cfg(feature = "macros")]
+inventory::collect!(RegisteredRoute);
+
+impl Router<BoxHandler> {
+    /// Creates a router of every handler annotated with `#[route]` in the program.
+    ///
+    /// Registrations are collected in no particular order, so equivalent patterns of the
+    /// same method should be avoided.
+    #[cfg(feature = "macros")]
+    pub fn from_registered() -> Self {
+        let mut router = Self::new();
+        for r in inventory::iter::<RegisteredRoute> {
+            router.push(Route {
+                method: Method::from_bytes(r.method.as_bytes()).unwrap(),
+                path: join_paths("/", r.path),
+                params: Vec::new(),
+                handler: (r.handler)(),
+                expects: None,
+                name: r.name.map(ToOwned::to_owned),
+                deprecation: None,
+                produces: None,
+                min_version: None,
+                layers: Vec::new(),
+                wraps: Vec::new(),
+                capture_body: None,
+                body_limit: None,
+                inherits: true,
+            });
+        }
+        router
     }
 
-    pub fn scope(mut self, mut router: Self) -> Self {
-        if let Some(routes) = router.routes.take() {
-            let r = &routes
+    /// Makes `from` an alias of the routes registered at `to`, carrying over the parameters
+    /// of the same name.
+    ///
+    /// With `redirect`, requests to `from` are answered with a `301` to the filled-in `to`
+    /// path instead of being handled by its routes directly.
+    pub fn alias(mut self, from: impl AsRef<str>, to: impl AsRef<str>, redirect: bool) -> Self {
+        let from = join_paths(&self.path, from.as_ref());
+        let to = join_paths(&self.path, to.as_ref());
+
+        if redirect {
+            let handler = BoxHandler::new(move |req: Request<Body>| {
+                let mut location = req
+                    .extensions()
+                    .get::<Params>()
+                    .and_then(|p| fill(&to, |name| Some(p.get(name).unwrap_or_default().into())))
+                    .unwrap_or_else(|| to.clone());
+                if let Some(query) = req.uri().query() {
+                    location = location + "?" + query;
+                }
+
+                let result = HeaderValue::from_str(&location).map(|location| {
+                    let mut res = status(StatusCode::MOVED_PERMANENTLY);
+                    res.headers_mut().insert(header::LOCATION, location);
+                    res
+                });
+                async move { Ok(result?) }
+            });
+            self.push(Route {
+                method: any_method(),
+                path: from,
+                params: Vec::new(),
+                handler,
+                expects: None,
+                name: None,
+                deprecation: None,
+                produces: None,
+                min_version: None,
+                layers: Vec::new(),
+                wraps: Vec::new(),
+                capture_body: None,
+                body_limit: None,
+                inherits: true,
+            });
+        } else {
+            let routes = self
+                .routes
                 .iter()
+                .filter(|r| r.path == to)
                 .cloned()
-                .map(|mut t| {
-                    t.1 = join_paths(&self.path, &t.1);
-                    t
-                })
                 .collect::<Vec<_>>();
-            self.routes
-                .get_or_insert_with(Vec::new)
-                .extend_from_slice(r);
+            for route in routes {
+                self.push(Route {
+                    path: from.clone(),
+                    ..route
+                });
+            }
         }
+
+        self
+    }
+
+    /// Mounts a tower service under the prefix, stripping the prefix from the request path
+    /// before delegating to it.
+    ///
+    /// The service is registered for any method as a catch-all below the prefix, so under
+    /// [`Precedence::Specificity`] regular routes which match the same path take precedence
+    /// over it, while under [`Precedence::Registration`] those registered after it do not.
+    ///
+    /// The service sees the rest of the path as sent, even with
+    /// [`decode_path`](Self::decode_path).
+    pub fn mount<S>(self, prefix: impl AsRef<str>, service: S) -> Self
+    where
+        S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + Sync + 'static,
+        S::Error: Into<Box<dyn StdError + Send + Sync>>,
+        S::Future: Send,
+    {
+        let service = BoxHandler::from_service(service);
+        let handler = BoxHandler::new(move |mut req: Request<Body>| {
+            let service = service.clone();
+            async move {
+                let rest = req
+                    .extensions()
+                    .get::<RawRest>()
+                    .map_or_else(String::new, |r| r.0.clone());
+                *req.uri_mut() = strip_prefix(req.uri(), &rest)?;
+                service.call(req).await
+            }
+        });
+
+        let prefix = prefix.as_ref();
+        self.any(prefix, handler.clone())
+            .any(join_paths(prefix, "*"), handler)
+    }
+
+    /// Serves the files under `dir` at `GET` and `HEAD` requests below the router's path,
+    /// with a `Content-Type` guessed from the extension, and `index.html` for directories.
+    ///
+    /// Paths with `..` segments, or resolving outside of `dir` through symlinks, are
+    /// rejected with `403`, like directories without an index. Missing files get `404`.
+    ///
+    /// This is only available on `Router<BoxHandler>`, the router that serves requests.
+    #[cfg(feature = "fs")]
+    pub fn serve_static(self, dir: impl Into<std::path::PathBuf>) -> Self {
+        let root = Arc::new(dir.into());
+        let handler = BoxHandler::new(move |req: Request<Body>| {
+            let root = root.clone();
+            async move {
+                let rest = req.extensions().get::<RawRest>().map_or("", |r| &r.0);
+                static_file(&root, rest).await
+            }
+        });
+
+        self.get("", handler.clone())
+            .head("", handler.clone())
+            .get("*path", handler.clone())
+            .head("*path", handler)
+    }
+
+    /// Turns the router into a tower service which drains when `signal` resolves: requests
+    /// in flight run to completion, while new ones are refused with `503` and
+    /// `Connection: close`.
+    ///
+    /// Requests arriving on kept-alive connections count as new, and the `Connection: close`
+    /// makes clients reconnect, to another instance once this one stops accepting. Pass
+    /// [`RouterService::drained`](crate::RouterService::drained) to hyper's `with_graceful_shutdown`, so the server stops
+    /// accepting and closes idle connections once the requests in flight finished.
+    pub fn into_service_with_shutdown<F>(self, signal: F) -> crate::RouterService
+    where
+        F: std::future::Future<Output = ()> + Send + 'static,
+    {
+        crate::RouterService::new(self, signal)
+    }
+
+    /// Handles the requests no route matches, instead of responding `404`.
+    ///
+    /// There is a single fallback, so this replaces one set by
+    /// [`fallback_service`](Self::fallback_service), and the other way round.
+    pub fn fallback(mut self, handler: BoxHandler) -> Self {
+        self.fallback.replace(handler);
+        self
+    }
+
+    /// Forwards the requests no route matches to a tower service, e.g. a legacy backend
+    /// during a migration. Replaces the [`fallback`](Self::fallback) handler if any.
+    pub fn fallback_service<S>(self, service: S) -> Self
+    where
+        S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + Sync + 'static,
+        S::Error: Into<Box<dyn StdError + Send + Sync>>,
+        S::Future: Send,
+    {
+        self.fallback(BoxHandler::from_service(service))
+    }
+
+    /// Applies a transform to every response after the handler runs, e.g. to stamp or strip
+    /// headers.
+    ///
+    /// Transforms run in registration order, after everything else the router does to the
+    /// response, and also apply to the responses the router produces itself, such as `404`.
+    /// Once [scoped](Self::scope), they only apply to the responses of the router's routes,
+    /// before those of the outer routers.
+    pub fn wrap<F>(mut self, f: F) -> Self
+    where
+        F: Fn(Response<Body>) -> Response<Body> + Send + Sync + 'static,
+    {
+        self.wraps.push(Wrap(Arc::new(f)));
+        self
+    }
+
+    /// Stamps `Server: value` on every response, or with `None` strips any `Server` header
+    /// the handlers set, so the server is not identified. Registered as a [`wrap`](Self::wrap).
+    pub fn server_header(self, value: Option<HeaderValue>) -> Self {
+        self.wrap(move |mut res| {
+            match &value {
+                Some(value) => res.headers_mut().insert(header::SERVER, value.clone()),
+                None => res.headers_mut().remove(header::SERVER),
+            };
+            res
+        })
+    }
+
+    /// Logs every dispatched request with `f`, labelled by the matched route's pattern
+    /// rather than the request path
+    pub fn access_log<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&AccessLog) + Send + Sync + 'static,
+    {
+        self.access_log.replace(Logger(Arc::new(f)));
         self
     }
 
-    pub fn serve_static(mut self, path: impl AsRef<str>) -> Self {
+    /// Calls `f` with the matched route's pattern and the handler's duration whenever a
+    /// handler takes longer than `threshold`, e.g. to log or alert on slow endpoints.
+    ///
+    /// Only the routed handler is timed, not the body or requests no route matched.
+    pub fn slow_requests<F>(mut self, threshold: Duration, f: F) -> Self
+    where
+        F: Fn(&str, Duration) + Send + Sync + 'static,
+    {
+        self.slow_requests
+            .replace((threshold, SlowHook(Arc::new(f))));
         self
     }
+
+    /// Dispatches the request to the matched route's handler
+    pub async fn dispatch(&self, req: Request<Body>) -> Response<Body> {
+        let start = Instant::now();
+        let method = req.method().clone();
+
+        let res = self.handle(req).await;
+        let res = self.wraps.iter().fold(res, |res, wrap| (wrap.0)(res));
+
+        if let Some(logger) = &self.access_log {
+            (logger.0)(&AccessLog {
+                method,
+                pattern: res
+                    .extensions()
+                    .get::<MatchedPath>()
+                    .map(|m| m.path.clone()),
+                status: res.status(),
+                duration: start.elapsed(),
+            });
+        }
+
+        res
+    }
+
+    async fn handle(&self, mut req: Request<Body>) -> Response<Body> {
+        let mut path = req.uri().path().to_owned();
+        if let Some(mode) = self.decode_path {
+            path = match decode_path(&path, mode) {
+                Some(path) => path,
+                None => return status(StatusCode::BAD_REQUEST),
+            };
+        }
+        if self.merge_slashes {
+            path = merge_slashes(&path);
+        }
+
+        let accept = req
+            .headers()
+            .get(header::ACCEPT)
+            .and_then(|v| v.to_str().ok());
+
+        let matches = self.matches(&path);
+        let (route, params) = match self.find_route(&matches, req.method(), accept) {
+            Some(found) => found,
+            None if accept.is_some() && self.lookup(&matches, req.method(), None).is_some() => {
+                return Error::NotAcceptable.into_response();
+            }
+            None => {
+                let allowed = self.allowed(&matches, &path);
+                if !allowed.is_empty() {
+                    let allow = allowed.iter().map(Method::as_str).collect::<Vec<_>>();
+                    let mut res = if self.auto_options && req.method() == Method::OPTIONS {
+                        status(StatusCode::NO_CONTENT)
+                    } else {
+                        status(StatusCode::METHOD_NOT_ALLOWED)
+                    };
+                    res.headers_mut().insert(
+                        header::ALLOW,
+                        HeaderValue::from_str(&allow.join(", ")).unwrap(),
+                    );
+                    return res;
+                }
+                return match &self.fallback {
+                    Some(fallback) => fallback.call(req).await.into_response(),
+                    None => status(StatusCode::NOT_FOUND),
+                };
+            }
+        };
+
+        let matched = MatchedPath {
+            path: route.path.clone(),
+            name: route.name.clone(),
+        };
+        req.extensions_mut().insert(matched.clone());
+
+        let start = Instant::now();
+        let head = req.method() == Method::HEAD && route.method == Method::GET;
+        let mut res = self.call(route, params, req).await;
+        if head {
+            strip_body(&mut res);
+        }
+        if let Some((threshold, hook)) = &self.slow_requests {
+            let duration = start.elapsed();
+            if duration > *threshold {
+                (hook.0)(&route.path, duration);
+            }
+        }
+        res.extensions_mut().insert(matched);
+
+        if let Some(sunset) = route.deprecation {
+            let headers = res.headers_mut();
+            headers.insert("deprecation", HeaderValue::from_static("true"));
+            if let Some(sunset) = sunset {
+                let sunset = httpdate::fmt_http_date(sunset);
+                headers.insert("sunset", HeaderValue::from_str(&sunset).unwrap());
+            }
+        }
+
+        route.wraps.iter().fold(res, |res, wrap| (wrap.0)(res))
+    }
+
+    async fn call(
+        &self,
+        route: &Route<BoxHandler>,
+        params: Params,
+        mut req: Request<Body>,
+    ) -> Response<Body> {
+        if route.min_version.is_some_and(|v| req.http_version() < v) {
+            return Error::HttpVersionNotSupported.into_response();
+        }
+
+        if let Some(expects) = &route.expects {
+            let valid = req
+                .content_type()
+                .filter(|m| {
+                    m.type_() == expects.type_()
+                        && (expects.subtype() == mime::STAR || m.subtype() == expects.subtype())
+                })
+                .is_some();
+
+            if !valid {
+                return Error::UnsupportedMediaType.into_response();
+            }
+        }
+
+        if let Some(limit) = route.body_limit.or(self.body_limit) {
+            if req.content_length().is_none() {
+                let body = match capture(&mut req, limit).await {
+                    Ok(body) => body,
+                    Err(e) => return e.into_response(),
+                };
+                *req.body_mut() = Body::from(body);
+            } else if req.content_length() > Some(limit as u64) {
+                return Error::PayloadTooLarge.into_response();
+            }
+            req.extensions_mut().insert(crate::BodyLimit(limit));
+        }
+
+        if let Some(limit) = route.capture_body.or(self.capture_body) {
+            let body = match capture(&mut req, limit).await {
+                Ok(body) => body,
+                Err(e) => return e.into_response(),
+            };
+            *req.body_mut() = Body::from(body.clone());
+            req.extensions_mut().insert(RawBody(body));
+        }
+
+        req.extensions_mut().insert(params);
+        if let Some(rest) = raw_rest(&route.path, req.uri().path(), self.merge_slashes) {
+            req.extensions_mut().insert(RawRest(rest));
+        }
+
+        let outer = self.layers.iter().filter(|_| route.inherits);
+        let middleware = outer
+            .chain(&route.layers)
+            .map(|layer| layer.0.clone())
+            .collect::<Vec<_>>();
+        if middleware.is_empty() {
+            return route.handler.call(req).await.into_response();
+        }
+
+        Next::new(middleware.into(), route.handler.clone())
+            .run(req)
+            .await
+            .into_response()
+    }
 }
 
-fn join_paths(a: &str, b: &str) -> String {
-    if b.is_empty() {
-        return a.to_owned();
+/// Responds with the file at the relative request path under the root, see
+/// [`Router::serve_static`]
+#[cfg(feature = "fs")]
+async fn static_file(root: &std::path::Path, rest: &str) -> Result<Response<Body>> {
+    use crate::ResponseExt;
+    use std::io::ErrorKind;
+
+    let mut path = root.to_path_buf();
+    for segment in rest.split('/').filter(|s| !s.is_empty() && *s != ".") {
+        if segment.to_ascii_lowercase().contains("%2f") {
+            return Ok(status(StatusCode::FORBIDDEN));
+        }
+        let segment = match decode_path(segment, PathDecoding::Strict) {
+            Some(segment) => segment,
+            None => return Ok(status(StatusCode::BAD_REQUEST)),
+        };
+        if segment == ".." || segment.contains(['/', '\\', ':', '\0']) {
+            return Ok(status(StatusCode::FORBIDDEN));
+        }
+        path.push(segment);
     }
-    a.trim_end_matches('/').to_owned() + "/" + b.trim_start_matches('/')
+
+    let (root, mut path) = match tokio::join!(
+        tokio::fs::canonicalize(root),
+        tokio::fs::canonicalize(&path)
+    ) {
+        (Ok(root), Ok(path)) => (root, path),
+        (_, Err(e)) if e.kind() == ErrorKind::NotFound => return Ok(status(StatusCode::NOT_FOUND)),
+        (Err(e), _) | (_, Err(e)) => return Err(e.into()),
+    };
+    if !path.starts_with(&root) {
+        return Ok(status(StatusCode::FORBIDDEN));
+    }
+
+    if tokio::fs::metadata(&path).await?.is_dir() {
+        path.push("index.html");
+        if !tokio::fs::metadata(&path).await.is_ok_and(|m| m.is_file()) {
+            return Ok(status(StatusCode::FORBIDDEN));
+        }
+    }
+
+    Response::file(path).await
 }
 
-#[cfg(test)]
-mod tests {
-    use super::Router;
+/// Drops the body of a `GET` response answering a `HEAD` request, declaring its length
+/// when it is known
+fn strip_body(res: &mut Response<Body>) {
+    use crate::body::HttpBody;
 
-    #[test]
-    fn routing() {
-        let app = Router::<usize>::new();
+    if let Some(len) = res.body().size_hint().exact() {
+        res.headers_mut()
+            .entry(header::CONTENT_LENGTH)
+            .or_insert_with(|| HeaderValue::from(len));
+    }
+    *res.body_mut() = Body::empty();
+}
 
-        let api = Router::new().path("/api").get("/", 1);
+/// Reads the whole body, failing with [`Error::PayloadTooLarge`] past `limit` bytes
+async fn capture(req: &mut Request<Body>, limit: usize) -> Result<Bytes> {
+    if req.content_length().is_some_and(|n| n > limit as u64) {
+        return Err(Error::PayloadTooLarge.into());
+    }
 
-        let v1 = Router::new().path("/v1").get("/", 2);
+    let mut body = BytesMut::new();
+    while let Some(chunk) = req.body_mut().next().await {
+        let chunk = chunk?;
+        if body.len() + chunk.len() > limit {
+            return Err(Error::PayloadTooLarge.into());
+        }
+        body.extend_from_slice(&chunk);
+    }
 
-        let v2 = Router::new().path("/v2").get("/", 3);
+    Ok(body.freeze())
+}
 
-        let app = app
-            .scope(api.scope(v1).scope(v2))
-            .get("/about", 1)
-            .post("/login", 2)
-            .delete("/logout", 3)
-            .any("/*", 4);
+/// The type of a path parameter, declared in a pattern as `{name:type}`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum ParamKind {
+    /// `{name}` or `{name:string}`, also `:name` and `*name`
+    String,
+    /// `{name:int}`, one or more ASCII digits
+    Int,
+    /// `{name:uuid}`, a hyphenated UUID
+    Uuid,
+    /// `{name:slug}`, lowercase alphanumerics separated by single hyphens
+    Slug,
+}
 
-        dbg!(app);
+impl ParamKind {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "string" => Some(Self::String),
+            "int" => Some(Self::Int),
+            "uuid" => Some(Self::Uuid),
+            "slug" => Some(Self::Slug),
+            _ => None,
+        }
+    }
+
+    pub fn accepts(&self, value: &str) -> bool {
+        match self {
+            Self::String => true,
+            Self::Int => !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()),
+            Self::Uuid => {
+                value.len() == 36
+                    && value.bytes().enumerate().all(|(i, b)| match i {
+                        8 | 13 | 18 | 23 => b == b'-',
+                        _ => b.is_ascii_hexdigit(),
+                    })
+            }
+            Self::Slug => value.split('-').all(|s| {
+                !s.is_empty()
+                    && s.bytes()
+                        .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit())
+            }),
+        }
+    }
+}
+
+/// The path parameters captured by the matched route
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Params(Vec<(String, String, ParamKind)>);
+
+/// The part of the request path the catch-all matched, as sent, before
+/// [`Router::decode_path`] decoded it
+#[derive(Clone, Debug)]
+struct RawRest(String);
+
+impl Params {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(k, _, _)| k == name)
+            .map(|(_, v, _)| v.as_str())
+    }
+
+    pub fn kind(&self, name: &str) -> Option<ParamKind> {
+        self.0
+            .iter()
+            .find(|(k, _, _)| k == name)
+            .map(|(_, _, kind)| *kind)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str, ParamKind)> {
+        self.0
+            .iter()
+            .map(|(k, v, kind)| (k.as_str(), v.as_str(), *kind))
+    }
+}
+
+/// Fills the pattern's parameters with the values `value` looks up by name, `None` when a
+/// lookup fails
+fn fill<'a, F>(pattern: &'a str, mut value: F) -> Option<String>
+where
+    F: FnMut(&str) -> Option<Cow<'a, str>>,
+{
+    pattern
+        .split('/')
+        .map(|s| {
+            let name = match s.chars().next() {
+                Some(':') | Some('*') => &s[1..],
+                Some('{') if s.ends_with('}') => s[1..s.len() - 1].split(':').next().unwrap(),
+                _ => return Some(Cow::Borrowed(s)),
+            };
+            value(name)
+        })
+        .collect::<Option<Vec<_>>>()
+        .map(|segments| segments.join("/"))
+}
+
+/// Percent-encodes everything but the unreserved characters of each segment, keeping the
+/// slashes between segments
+fn encode_path(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for b in value.bytes() {
+        match b {
+            b'/' | b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(b as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+/// Percent-encodes a value for a single segment, slashes included
+fn encode_segment(value: &str) -> String {
+    encode_path(value).replace('/', "%2F")
+}
+
+/// The segments of the undecoded path the catch-all of the pattern matched, which line up
+/// with the decoded ones since `%2F` stays encoded
+fn raw_rest(pattern: &str, path: &str, merge: bool) -> Option<String> {
+    let at = pattern.split('/').position(|s| s.starts_with('*'))?;
+    let path = if merge {
+        Cow::Owned(merge_slashes(path))
+    } else {
+        Cow::Borrowed(path)
+    };
+    path.splitn(at + 1, '/').nth(at).map(str::to_owned)
+}
+
+/// Replaces the path of the URI with the unmatched rest, keeping the query
+fn strip_prefix(uri: &Uri, rest: &str) -> Result<Uri> {
+    let path_and_query = match uri.query() {
+        Some(query) => format!("/{}?{}", rest, query),
+        None => format!("/{}", rest),
+    };
+
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query.replace(path_and_query.parse()?);
+    Ok(Uri::from_parts(parts)?)
+}
+
+fn status(status: StatusCode) -> Response<Body> {
+    let mut res = Response::default();
+    *res.status_mut() = status;
+    res
+}
+
+fn any_method() -> Method {
+    Method::from_bytes(b"*").unwrap()
+}
+
+fn merge_slashes(path: &str) -> String {
+    let mut merged = String::with_capacity(path.len());
+    for c in path.chars() {
+        if c != '/' || !merged.ends_with('/') {
+            merged.push(c);
+        }
+    }
+    merged
+}
+
+/// Percent-decodes a path except for `%2F`, `None` when it is malformed in strict mode
+fn decode_path(path: &str, mode: PathDecoding) -> Option<String> {
+    fn hex(b: u8) -> Option<u8> {
+        (b as char).to_digit(16).map(|d| d as u8)
+    }
+
+    let bytes = path.as_bytes();
+    let mut decoded = String::with_capacity(path.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        // a run of consecutive escapes, which together may encode a multi-byte char
+        let start = i;
+        let mut run = Vec::new();
+        while bytes.get(i) == Some(&b'%') {
+            let byte = match (
+                bytes.get(i + 1).and_then(|b| hex(*b)),
+                bytes.get(i + 2).and_then(|b| hex(*b)),
+            ) {
+                (Some(h), Some(l)) => h << 4 | l,
+                _ => break,
+            };
+            if byte == b'/' {
+                break;
+            }
+            run.push(byte);
+            i += 3;
+        }
+
+        if i > start {
+            match String::from_utf8(run) {
+                Ok(s) => decoded.push_str(&s),
+                Err(_) if mode == PathDecoding::Strict => return None,
+                Err(_) => decoded.push_str(&path[start..i]),
+            }
+            continue;
+        }
+
+        if bytes[i] == b'%' {
+            let encoded_slash = path[i..]
+                .get(..3)
+                .is_some_and(|s| s.eq_ignore_ascii_case("%2f"));
+            if !encoded_slash && mode == PathDecoding::Strict {
+                return None;
+            }
+            let len = if encoded_slash { 3 } else { 1 };
+            decoded.push_str(&path[i..i + len]);
+            i += len;
+            continue;
+        }
+
+        let next = path[i..].find('%').map_or(path.len(), |n| i + n);
+        decoded.push_str(&path[i..next]);
+        i = next;
+    }
+
+    Some(decoded)
+}
+
+/// Matches the path against a compiled key, capturing the values of its parameters.
+///
+/// A `:` segment takes one non-empty path segment, a trailing `*` the rest of the path.
+fn match_key<'a>(key: &str, path: &'a str) -> Option<Vec<&'a str>> {
+    let mut values = Vec::new();
+    let mut rest = Some(path);
+
+    for segment in key.split('/') {
+        let remaining = rest?;
+        if segment == "*" {
+            values.push(remaining);
+            return Some(values);
+        }
+
+        let (head, tail) = match remaining.split_once('/') {
+            Some((head, tail)) => (head, Some(tail)),
+            None => (remaining, None),
+        };
+        match segment {
+            ":" if !head.is_empty() => values.push(head),
+            _ if segment == head && segment != ":" => {}
+            _ => return None,
+        }
+        rest = tail;
+    }
+
+    rest.is_none().then_some(values)
+}
+
+/// Whether the compiled key `a` matches every path `b` does, with its typed parameters
+/// accepting whatever values those of `b` accept
+fn covers(a: (&str, &[(String, ParamKind)]), b: (&str, &[(String, ParamKind)])) -> bool {
+    let mut a_kinds = a.1.iter().map(|(_, kind)| *kind);
+    let mut b_kinds = b.1.iter().map(|(_, kind)| *kind);
+    let mut b_segments = b.0.split('/');
+
+    for segment in a.0.split('/') {
+        if segment == "*" {
+            let kind = a_kinds.next().unwrap_or(ParamKind::String);
+            let rest = b_segments.collect::<Vec<_>>();
+            // The catch-all needs the path to go on, and a typed one a catch-all alike
+            return match rest.as_slice() {
+                [] => false,
+                _ if kind == ParamKind::String => true,
+                ["*"] => b_kinds.next_back() == Some(kind),
+                _ => false,
+            };
+        }
+
+        let other = match b_segments.next() {
+            Some(other) => other,
+            None => return false,
+        };
+        let covered = match (segment, other) {
+            (_, "*") => false,
+            (":", ":") => {
+                let (a, b) = (a_kinds.next(), b_kinds.next());
+                a == Some(ParamKind::String) || a == b
+            }
+            (":", other) => {
+                !other.is_empty() && a_kinds.next().is_some_and(|kind| kind.accepts(other))
+            }
+            (_, ":") => false,
+            (segment, other) => segment == other,
+        };
+        if !covered {
+            return false;
+        }
+    }
+
+    b_segments.next().is_none()
+}
+
+/// The first segment of a path or compiled key, empty for `/`
+fn first_segment(path: &str) -> &str {
+    path.split('/').nth(1).unwrap_or_default()
+}
+
+/// The specificity of a compiled key, segment by segment: static segments rank before
+/// parameters, which rank before catch-alls
+fn rank(key: &str) -> Vec<u8> {
+    key.split('/')
+        .map(|segment| match segment {
+            ":" => 1,
+            "*" => 2,
+            _ => 0,
+        })
+        .collect()
+}
+
+fn join_paths(a: &str, b: &str) -> String {
+    if b.is_empty() {
+        return a.to_owned();
+    }
+    a.trim_end_matches('/').to_owned() + "/" + b.trim_start_matches('/')
+}
+
+/// Compiles a pattern into its tree key and typed parameters.
+///
+/// The key strips the parameter names, so that `/users/:id` and `/users/{name:slug}` share a
+/// tree node.
+///
+/// # Panics
+///
+/// Panics when a `{name:type}` segment declares an unknown type.
+fn compile(pattern: &str) -> (String, Vec<(String, ParamKind)>) {
+    let mut params = Vec::new();
+
+    let key = pattern
+        .split('/')
+        .map(|s| match s.chars().next() {
+            Some(c @ ':') | Some(c @ '*') => {
+                params.push((s[1..].to_owned(), ParamKind::String));
+                c.to_string()
+            }
+            Some('{') if s.ends_with('}') => {
+                let (name, kind) = match s[1..s.len() - 1].split_once(':') {
+                    Some((name, ty)) => (
+                        name,
+                        ParamKind::parse(ty).unwrap_or_else(|| {
+                            panic!("unknown parameter type `{}` in `{}`", ty, pattern)
+                        }),
+                    ),
+                    None => (&s[1..s.len() - 1], ParamKind::String),
+                };
+                params.push((name.to_owned(), kind));
+                ":".to_owned()
+            }
+            _ => s.to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+
+    (key, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Match, ParamKind, Precedence, Router};
+    use crate::{
+        body, header, service::service_fn, Body, BoxHandler, Method, Request, RequestExt, Response,
+        StatusCode,
+    };
+    use std::convert::Infallible;
+
+    #[test]
+    fn routing() {
+        let app = Router::<usize>::new();
+
+        let api = Router::new().path("/api").get("/", 1);
+
+        let v1 = Router::new().path("/v1").get("/", 2);
+
+        let v2 = Router::new().path("/v2").get("/", 3);
+
+        let app = app
+            .scope(api.scope(v1).scope(v2))
+            .get("/about", 1)
+            .post("/login", 2)
+            .delete("/logout", 3)
+            .any("/*", 4);
+
+        dbg!(app);
+    }
+
+    #[test]
+    fn find() {
+        let app = Router::<usize>::new()
+            .get("/users/:id", 1)
+            .any("/users/:id", 2)
+            .get("/files/*path", 3);
+
+        assert_eq!(
+            app.find(&Method::GET, "/users/42"),
+            Match::Found(&1, vec![("id", "42")])
+        );
+        assert_eq!(
+            app.find(&Method::DELETE, "/users/42"),
+            Match::Found(&2, vec![("id", "42")])
+        );
+        assert_eq!(
+            app.find(&Method::GET, "/files/a/b.txt"),
+            Match::Found(&3, vec![("path", "a/b.txt")])
+        );
+        assert_eq!(
+            app.find(&Method::POST, "/files/a"),
+            Match::MethodNotAllowed(vec![Method::GET, Method::HEAD, Method::OPTIONS])
+        );
+        assert_eq!(app.find(&Method::GET, "/posts"), Match::NotFound);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn client_manifest() {
+        let app = Router::<usize>::new()
+            .post("/users/{id:int}/posts/:slug", 1)
+            .named("create_post")
+            .expects(mime::APPLICATION_JSON)
+            .produces(mime::APPLICATION_JSON)
+            .get("/health", 2);
+
+        let manifest = app.client_manifest();
+        assert_eq!(manifest.len(), 2);
+        assert_eq!(
+            serde_json::to_value(&manifest[0]).unwrap(),
+            serde_json::json!({
+                "method": "POST",
+                "path": "/users/{id:int}/posts/:slug",
+                "name": "create_post",
+                "params": [
+                    { "name": "id", "kind": "int" },
+                    { "name": "slug", "kind": "string" },
+                ],
+                "consumes": "application/json",
+                "produces": "application/json",
+                "deprecated": false,
+            })
+        );
+        assert_eq!(manifest[1].method, "GET");
+        assert!(manifest[1].params.is_empty());
+    }
+
+    #[test]
+    fn precedence() {
+        let app = |precedence| {
+            Router::<usize>::new()
+                .get("/users/*rest", 3)
+                .get("/users/:id", 2)
+                .post("/users/{id:int}/edit", 4)
+                .get("/users/new", 1)
+                .precedence(precedence)
+        };
+
+        let specific = app(Precedence::Specificity);
+        assert_eq!(
+            specific.find(&Method::GET, "/users/new"),
+            Match::Found(&1, vec![])
+        );
+        assert_eq!(
+            specific.find(&Method::GET, "/users/42"),
+            Match::Found(&2, vec![("id", "42")])
+        );
+        // No `GET` route of `/users/{id:int}/edit`, so the catch-all takes it
+        assert_eq!(
+            specific.find(&Method::GET, "/users/42/edit"),
+            Match::Found(&3, vec![("rest", "42/edit")])
+        );
+        assert_eq!(
+            specific.match_order(),
+            [
+                "/users/new",
+                "/users/:id",
+                "/users/{id:int}/edit",
+                "/users/*rest"
+            ]
+        );
+
+        let registered = app(Precedence::Registration);
+        assert_eq!(
+            registered.find(&Method::GET, "/users/new"),
+            Match::Found(&3, vec![("rest", "new")])
+        );
+        assert_eq!(
+            registered.match_order(),
+            [
+                "/users/*rest",
+                "/users/:id",
+                "/users/{id:int}/edit",
+                "/users/new"
+            ]
+        );
+    }
+
+    #[test]
+    fn unreachable_across_patterns() {
+        let app = |precedence| {
+            Router::<usize>::new()
+                .any("/*rest", 0)
+                .get("/users/new", 1)
+                .get("/users/:id", 2)
+                .precedence(precedence)
+        };
+
+        assert_eq!(
+            app(Precedence::Registration).unreachable(),
+            ["GET /users/new", "GET /users/:id"]
+        );
+        assert!(app(Precedence::Specificity).unreachable().is_empty());
+
+        let app = Router::<usize>::new()
+            .get("/users/:id", 1)
+            .get("/users/new", 2)
+            .post("/users/new", 3)
+            .get("/posts/{id:int}", 4)
+            .get("/posts/:id", 5)
+            .get("/files/*path", 6)
+            .get("/files/{n:int}", 7)
+            .get("/files/:name/raw", 8)
+            .get("/files", 9)
+            .precedence(Precedence::Registration);
+        assert_eq!(
+            app.unreachable(),
+            [
+                "GET /users/new",
+                "GET /files/{n:int}",
+                "GET /files/:name/raw"
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_by_first_segment() {
+        let app = |precedence| {
+            Router::<usize>::new()
+                .get("/:page", 1)
+                .get("/users/:id", 2)
+                .any("/*path", 3)
+                .get("/users", 4)
+                .get("/", 5)
+                .precedence(precedence)
+        };
+
+        let specific = app(Precedence::Specificity);
+        assert_eq!(
+            specific.find(&Method::GET, "/users"),
+            Match::Found(&4, vec![])
+        );
+        assert_eq!(
+            specific.find(&Method::GET, "/users/7"),
+            Match::Found(&2, vec![("id", "7")])
+        );
+        assert_eq!(
+            specific.find(&Method::POST, "/users/7"),
+            Match::Found(&3, vec![("path", "users/7")])
+        );
+        assert_eq!(specific.find(&Method::GET, "/"), Match::Found(&5, vec![]));
+        assert_eq!(
+            specific.find(&Method::GET, "/about"),
+            Match::Found(&1, vec![("page", "about")])
+        );
+
+        let registered = app(Precedence::Registration);
+        assert_eq!(
+            registered.find(&Method::GET, "/users"),
+            Match::Found(&1, vec![("page", "users")])
+        );
+        assert_eq!(
+            registered.find(&Method::GET, "/users/7"),
+            Match::Found(&2, vec![("id", "7")])
+        );
+        assert_eq!(
+            registered.find(&Method::GET, "/"),
+            Match::Found(&3, vec![("path", "")])
+        );
+    }
+
+    #[test]
+    fn typed_params() {
+        let app = Router::<usize>::new()
+            .get("/users/{id:int}", 1)
+            .get("/users/{name:slug}", 2)
+            .get("/posts/{id:int}/comments/:comment", 3);
+
+        let (route, params) = app
+            .find_route(&app.matches("/users/42"), &Method::GET, None)
+            .unwrap();
+        assert_eq!(route.handler, 1);
+        assert_eq!(params.get("id"), Some("42"));
+        assert_eq!(params.kind("id"), Some(ParamKind::Int));
+
+        let (route, params) = app
+            .find_route(&app.matches("/users/jane-doe"), &Method::GET, None)
+            .unwrap();
+        assert_eq!(route.handler, 2);
+        assert_eq!(params.get("name"), Some("jane-doe"));
+
+        assert!(app
+            .find_route(&app.matches("/users/Jane_Doe"), &Method::GET, None)
+            .is_none());
+
+        let (route, params) = app
+            .find_route(&app.matches("/posts/7/comments/x"), &Method::GET, None)
+            .unwrap();
+        assert_eq!(route.handler, 3);
+        assert_eq!(params.get("comment"), Some("x"));
+        assert_eq!(params.kind("comment"), Some(ParamKind::String));
+
+        assert!(app
+            .find_route(&app.matches("/posts/7a/comments/x"), &Method::GET, None)
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn params() -> anyhow::Result<()> {
+        #[derive(serde::Deserialize)]
+        struct Post {
+            id: u32,
+            slug: String,
+        }
+
+        let app = Router::new().get(
+            "/users/{id:int}/posts/:slug",
+            BoxHandler::new(|req: Request<Body>| async move {
+                let post = req.params::<Post>()?;
+                let value = req.params::<serde_json::Value>()?;
+                assert_eq!(value, serde_json::json!({ "id": 42, "slug": "hello" }));
+                Ok(Response::new(format!("{} {}", post.id, post.slug).into()))
+            }),
+        );
+
+        let res = app
+            .dispatch(
+                Request::builder()
+                    .uri("/users/42/posts/hello")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(body::to_bytes(res.into_body()).await?, "42 hello");
+
+        Ok(())
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn capture_body() -> anyhow::Result<()> {
+        let app = Router::new()
+            .post(
+                "/",
+                BoxHandler::new(|req: Request<Body>| async move {
+                    let raw = req.raw_body().cloned().unwrap();
+                    match req.json::<serde_json::Value>().await {
+                        Ok(_) => Ok(Response::new(Body::empty())),
+                        Err(_) => Ok(Response::new(Body::from(raw))),
+                    }
+                }),
+            )
+            .capture_body(16);
+
+        let post = |body: &'static str| {
+            Request::post("/")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let res = app.dispatch(post("{\"a\": ")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(body::to_bytes(res.into_body()).await?, "{\"a\": ");
+
+        let res = app.dispatch(post("{\"a\": \"too long to keep\"}")).await;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let app = Router::new().scope(app).capture_body(64);
+        let res = app.dispatch(post("{\"a\": \"too long to keep\"}")).await;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn alias() -> anyhow::Result<()> {
+        let show = || {
+            BoxHandler::new(|req: Request<Body>| async move {
+                let id = req.extensions().get::<super::Params>().unwrap().get("id");
+                Ok(Response::new(Body::from(id.unwrap().to_owned())))
+            })
+        };
+
+        let app = Router::new()
+            .get("/posts/{id:int}", show())
+            .alias("/articles/{id:int}", "/posts/{id:int}", false)
+            .alias("/blog/:id", "/posts/{id:int}", true);
+
+        let res = app
+            .dispatch(Request::get("/articles/7").body(Body::empty())?)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(body::to_bytes(res.into_body()).await?, "7");
+
+        let res = app
+            .dispatch(Request::get("/articles/x").body(Body::empty())?)
+            .await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let res = app
+            .dispatch(Request::get("/blog/7?ref=feed").body(Body::empty())?)
+            .await;
+        assert_eq!(res.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(res.headers()[header::LOCATION], "/posts/7?ref=feed");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn access_log() -> anyhow::Result<()> {
+        use std::sync::{Arc, Mutex};
+
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let app = Router::new()
+            .get(
+                "/users/{id:int}",
+                BoxHandler::new(|req: Request<Body>| async move {
+                    let matched = req.extensions().get::<super::MatchedPath>().unwrap();
+                    Ok(Response::new(Body::from(matched.as_str().to_owned())))
+                }),
+            )
+            .access_log({
+                let lines = lines.clone();
+                move |log| {
+                    lines.lock().unwrap().push(format!(
+                        "{} {} {}",
+                        log.method,
+                        log.pattern.as_deref().unwrap_or("-"),
+                        log.status.as_u16()
+                    ))
+                }
+            });
+
+        let res = app
+            .dispatch(Request::get("/users/42").body(Body::empty())?)
+            .await;
+        assert_eq!(body::to_bytes(res.into_body()).await?, "/users/{id:int}");
+        app.dispatch(Request::get("/users/x").body(Body::empty())?)
+            .await;
+
+        assert_eq!(
+            *lines.lock().unwrap(),
+            ["GET /users/{id:int} 200", "GET - 404"]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn method_not_allowed() -> anyhow::Result<()> {
+        let ok = || BoxHandler::new(|_: Request<Body>| async { Ok(Response::new(Body::empty())) });
+        let app = Router::new()
+            .get("/users/{id:int}", ok())
+            .delete("/users/{id:int}", ok())
+            .post("/users/{name}", ok());
+
+        let res = app
+            .dispatch(Request::put("/users/7").body(Body::empty())?)
+            .await;
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(
+            res.headers()[header::ALLOW],
+            "GET, DELETE, POST, HEAD, OPTIONS"
+        );
+
+        let res = app
+            .dispatch(Request::put("/users/x").body(Body::empty())?)
+            .await;
+        assert_eq!(res.headers()[header::ALLOW], "POST, OPTIONS");
+
+        let res = app
+            .dispatch(Request::put("/posts").body(Body::empty())?)
+            .await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn auto_options() -> anyhow::Result<()> {
+        let ok = || BoxHandler::new(|_: Request<Body>| async { Ok(Response::new(Body::empty())) });
+        let app = |auto_options| {
+            Router::new()
+                .get("/users", ok())
+                .post("/users", ok())
+                .delete("/users/:id", ok())
+                .options("/custom", ok())
+                .put("/custom", ok())
+                .auto_options(auto_options)
+        };
+
+        let res = app(true)
+            .dispatch(Request::options("/users").body(Body::empty())?)
+            .await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(res.headers()[header::ALLOW], "GET, POST, HEAD, OPTIONS");
+
+        let res = app(true)
+            .dispatch(Request::options("/custom").body(Body::empty())?)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(!res.headers().contains_key(header::ALLOW));
+
+        let res = app(true)
+            .dispatch(Request::options("*").body(Body::empty())?)
+            .await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            res.headers()[header::ALLOW],
+            "GET, POST, DELETE, OPTIONS, PUT, HEAD"
+        );
+
+        let res = app(true)
+            .dispatch(Request::options("/missing").body(Body::empty())?)
+            .await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let res = app(false)
+            .dispatch(Request::options("/users").body(Body::empty())?)
+            .await;
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(res.headers()[header::ALLOW], "GET, POST, HEAD");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn auto_head() -> anyhow::Result<()> {
+        let app = |auto_head| {
+            Router::new()
+                .get(
+                    "/x",
+                    BoxHandler::new(|_: Request<Body>| async {
+                        Ok(Response::builder()
+                            .header("x-served-by", "get")
+                            .body(Body::from("hello"))?)
+                    }),
+                )
+                .auto_head(auto_head)
+        };
+
+        assert!(matches!(
+            app(true).find(&Method::HEAD, "/x"),
+            Match::Found(..)
+        ));
+        let res = app(true)
+            .dispatch(Request::head("/x").body(Body::empty())?)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()["x-served-by"], "get");
+        assert_eq!(res.headers()[header::CONTENT_LENGTH], "5");
+        assert!(body::to_bytes(res.into_body()).await?.is_empty());
+
+        assert!(matches!(
+            app(false).find(&Method::HEAD, "/x"),
+            Match::MethodNotAllowed(methods) if methods == [Method::GET, Method::OPTIONS]
+        ));
+        let res = app(false)
+            .dispatch(Request::head("/x").body(Body::empty())?)
+            .await;
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(res.headers()[header::ALLOW], "GET, OPTIONS");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn slow_requests() -> anyhow::Result<()> {
+        use std::sync::{Arc, Mutex};
+        use std::time::Duration;
+
+        let slow = Arc::new(Mutex::new(Vec::new()));
+        let app = Router::new()
+            .get(
+                "/fast",
+                BoxHandler::new(|_: Request<Body>| async { Ok(Response::new(Body::empty())) }),
+            )
+            .get(
+                "/slow/{id:int}",
+                BoxHandler::new(|_: Request<Body>| async {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    Ok(Response::new(Body::empty()))
+                }),
+            )
+            .slow_requests(Duration::from_millis(20), {
+                let slow = slow.clone();
+                move |pattern, duration| slow.lock().unwrap().push((pattern.to_owned(), duration))
+            });
+
+        for path in ["/fast", "/slow/7", "/missing"] {
+            app.dispatch(Request::get(path).body(Body::empty())?).await;
+        }
+
+        let slow = slow.lock().unwrap();
+        assert_eq!(slow.len(), 1);
+        assert_eq!(slow[0].0, "/slow/{id:int}");
+        assert!(slow[0].1 >= Duration::from_millis(50));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn middleware() -> anyhow::Result<()> {
+        use crate::Next;
+        use std::sync::{Arc, Mutex};
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let tag = |name: &'static str| {
+            let log = log.clone();
+            move |req: Request<Body>, next: Next| {
+                log.lock().unwrap().push(name);
+                next.run(req)
+            }
+        };
+        let ok = || BoxHandler::new(|_: Request<Body>| async { Ok(Response::new(Body::empty())) });
+
+        let v1 = Router::new()
+            .path("/v1")
+            .inherit(true)
+            .get("/x", ok())
+            .with(tag("v1"));
+        let api = Router::new()
+            .path("/api")
+            .inherit(true)
+            .with(tag("api"))
+            .scope(v1);
+        let admin = Router::new()
+            .path("/admin")
+            .get("/x", ok())
+            .with(tag("admin"))
+            .with(|req: Request<Body>, next: Next| async move {
+                if req.headers().contains_key(header::AUTHORIZATION) {
+                    next.run(req).await
+                } else {
+                    Ok(super::status(StatusCode::UNAUTHORIZED))
+                }
+            });
+        let app = Router::new()
+            .with(tag("root"))
+            .get("/", ok())
+            .scope(api)
+            .scope(admin)
+            .with(tag("late"));
+
+        let dispatch = |req: Request<Body>| {
+            let app = &app;
+            let log = log.clone();
+            async move {
+                log.lock().unwrap().clear();
+                let status = app.dispatch(req).await.status();
+                let log = log.lock().unwrap().clone();
+                (status, log)
+            }
+        };
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+
+        assert_eq!(
+            dispatch(get("/")).await,
+            (StatusCode::OK, vec!["root", "late"])
+        );
+        assert_eq!(
+            dispatch(get("/api/v1/x")).await,
+            (StatusCode::OK, vec!["root", "late", "api", "v1"])
+        );
+        assert_eq!(
+            dispatch(get("/admin/x")).await,
+            (StatusCode::UNAUTHORIZED, vec!["admin"])
+        );
+        let authorized = Request::get("/admin/x")
+            .header(header::AUTHORIZATION, "Bearer t")
+            .body(Body::empty())?;
+        assert_eq!(dispatch(authorized).await, (StatusCode::OK, vec!["admin"]));
+        assert_eq!(
+            dispatch(get("/missing")).await,
+            (StatusCode::NOT_FOUND, vec![])
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn middleware_inheritance() -> anyhow::Result<()> {
+        use crate::Next;
+        use std::sync::{Arc, Mutex};
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let tag = |name: &'static str| {
+            let log = log.clone();
+            move |req: Request<Body>, next: Next| {
+                log.lock().unwrap().push(name);
+                next.run(req)
+            }
+        };
+        let ok = || BoxHandler::new(|_: Request<Body>| async { Ok(Response::new(Body::empty())) });
+
+        // `private` opts out, so neither `parent` nor `grand` wrap its routes, while the
+        // routes of `public` and `parent` itself get every enclosing layer
+        let private = Router::new()
+            .path("/private")
+            .get("/x", ok())
+            .with(tag("private"));
+        let public = Router::new()
+            .path("/public")
+            .inherit(true)
+            .get("/x", ok())
+            .with(tag("public"));
+        let parent = Router::new()
+            .path("/parent")
+            .inherit(true)
+            .get("/x", ok())
+            .with(tag("parent"))
+            .scope(private)
+            .scope(public);
+        let grand = Router::new().with(tag("grand")).scope(parent);
+
+        for (path, expected) in [
+            ("/parent/private/x", vec!["private"]),
+            ("/parent/public/x", vec!["grand", "parent", "public"]),
+            ("/parent/x", vec!["grand", "parent"]),
+        ] {
+            log.lock().unwrap().clear();
+            let res = grand
+                .dispatch(Request::get(path).body(Body::empty())?)
+                .await;
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(*log.lock().unwrap(), expected, "{}", path);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn deprecated() -> anyhow::Result<()> {
+        use std::time::{Duration, SystemTime};
+
+        let ok = || BoxHandler::new(|_: Request<Body>| async { Ok(Response::new(Body::empty())) });
+        let sunset = SystemTime::UNIX_EPOCH + Duration::from_secs(1_767_225_600);
+
+        let app = Router::new()
+            .get("/v1/users", ok())
+            .deprecated(Some(sunset))
+            .get("/v1/posts", ok())
+            .deprecated(None)
+            .get("/v2/users", ok());
+
+        let res = app
+            .dispatch(Request::get("/v1/users").body(Body::empty())?)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()["deprecation"], "true");
+        assert_eq!(res.headers()["sunset"], "Thu, 01 Jan 2026 00:00:00 GMT");
+
+        let res = app
+            .dispatch(Request::get("/v1/posts").body(Body::empty())?)
+            .await;
+        assert_eq!(res.headers()["deprecation"], "true");
+        assert!(res.headers().get("sunset").is_none());
+
+        let res = app
+            .dispatch(Request::get("/v2/users").body(Body::empty())?)
+            .await;
+        assert!(res.headers().get("deprecation").is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn merge_slashes() -> anyhow::Result<()> {
+        let show = || {
+            BoxHandler::new(|req: Request<Body>| async move {
+                let id = req.extensions().get::<super::Params>().unwrap().get("id");
+                Ok(Response::new(Body::from(id.unwrap().to_owned())))
+            })
+        };
+        let get = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+
+        let app = Router::new().get("/api/users/:id", show());
+        let res = app.dispatch(get("/api//users/1")).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let app = app.merge_slashes(true);
+        let res = app.dispatch(get("//api//users///1")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(body::to_bytes(res.into_body()).await?, "1");
+
+        let res = app.dispatch(get("/api//users/a%2F%2Fb")).await;
+        assert_eq!(body::to_bytes(res.into_body()).await?, "a%2F%2Fb");
+
+        Ok(())
+    }
+
+    #[test]
+    fn unreachable() {
+        let app = Router::<usize>::new()
+            .any("/*path", 0)
+            .get("/users/:id", 1)
+            .get("/users/{id:int}", 2)
+            .post("/users/{id:int}", 3)
+            .get("/posts/{id:int}", 4)
+            .get("/posts/:id", 5)
+            .any("/*file", 6);
+
+        assert_eq!(
+            app.unreachable(),
+            ["* /*file", "GET /users/{id:int}"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+        );
+        assert!(Router::<usize>::new().get("/", 0).unreachable().is_empty());
+
+        let app = Router::<usize>::new()
+            .any("/*", 0)
+            .get("/about", 1)
+            .post("/login", 2)
+            .precedence(Precedence::Registration);
+        assert_eq!(app.unreachable(), ["GET /about", "POST /login"]);
+    }
+
+    #[tokio::test]
+    async fn min_version() -> anyhow::Result<()> {
+        use crate::Version;
+
+        let ok = BoxHandler::new(|_: Request<Body>| async { Ok(Response::new(Body::empty())) });
+        let app = Router::new()
+            .get("/ws", ok.clone())
+            .min_version(Version::HTTP_11)
+            .get("/", ok);
+        let get = |path: &str, version: Version| {
+            Request::get(path)
+                .version(version)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let res = app.dispatch(get("/ws", Version::HTTP_10)).await;
+        assert_eq!(res.status(), StatusCode::HTTP_VERSION_NOT_SUPPORTED);
+        for version in [Version::HTTP_11, Version::HTTP_2] {
+            assert_eq!(
+                app.dispatch(get("/ws", version)).await.status(),
+                StatusCode::OK
+            );
+        }
+        assert_eq!(
+            app.dispatch(get("/", Version::HTTP_10)).await.status(),
+            StatusCode::OK
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn body_limit() -> anyhow::Result<()> {
+        let echo = BoxHandler::new(|req: Request<Body>| async move {
+            let body = Request::bytes(req.into_body()).await?;
+            Ok(Response::new(Body::from(body)))
+        });
+        let app = Router::new().post("/", echo).body_limit(8);
+        let post = |body: Body, len: Option<usize>| {
+            let mut req = Request::post("/");
+            if let Some(len) = len {
+                req = req.header(header::CONTENT_LENGTH, len);
+            }
+            req.body(body).unwrap()
+        };
+        let chunked = |chunks: &'static [&'static str]| {
+            let chunks = chunks.iter().copied().map(Ok::<_, std::io::Error>);
+            Body::wrap_stream(futures_util::stream::iter(chunks))
+        };
+
+        let res = app.dispatch(post(Body::from("123456789"), Some(9))).await;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let res = app.dispatch(post(chunked(&["12345", "6789"]), None)).await;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let res = app.dispatch(post(Body::from("12345678"), Some(8))).await;
+        assert_eq!(body::to_bytes(res.into_body()).await?, "12345678");
+        let res = app.dispatch(post(chunked(&["1234", "5678"]), None)).await;
+        assert_eq!(body::to_bytes(res.into_body()).await?, "12345678");
+
+        let app = Router::new().scope(app).body_limit(64);
+        let res = app.dispatch(post(Body::from("123456789"), Some(9))).await;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let res = app.dispatch(post(chunked(&["12345", "6789"]), None)).await;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "fs")]
+    #[tokio::test]
+    async fn serve_static() -> anyhow::Result<()> {
+        let base = std::env::temp_dir().join("hyperstone-serve-static");
+        let _ = std::fs::remove_dir_all(&base);
+        let root = base.join("public");
+        std::fs::create_dir_all(root.join("docs"))?;
+        std::fs::create_dir_all(root.join("empty"))?;
+        std::fs::write(root.join("docs/index.html"), "<h1>docs</h1>")?;
+        std::fs::write(root.join("app.js"), "run()")?;
+        std::fs::write(root.join("%41.txt"), "escaped")?;
+        std::fs::write(base.join("secret.txt"), "secret")?;
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(base.join("secret.txt"), root.join("link.txt"))?;
+
+        let app = Router::new().path("/static").serve_static(&root);
+        let get = |path: &str| app.dispatch(Request::get(path).body(Body::empty()).unwrap());
+
+        let res = get("/static/app.js").await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "text/javascript");
+        assert_eq!(body::to_bytes(res.into_body()).await?, "run()");
+
+        let res = get("/static/docs").await;
+        assert_eq!(body::to_bytes(res.into_body()).await?, "<h1>docs</h1>");
+
+        for path in [
+            "/static/../secret.txt",
+            "/static/docs/../../secret.txt",
+            "/static/%2e%2e/secret.txt",
+            "/static/..%2Fsecret.txt",
+            "/static/empty",
+        ] {
+            assert_eq!(get(path).await.status(), StatusCode::FORBIDDEN, "{}", path);
+        }
+        #[cfg(unix)]
+        assert_eq!(
+            get("/static/link.txt").await.status(),
+            StatusCode::FORBIDDEN
+        );
+
+        assert_eq!(
+            get("/static/missing.js").await.status(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(get("/static/%zz").await.status(), StatusCode::BAD_REQUEST);
+
+        let decoded = Router::new()
+            .path("/static")
+            .serve_static(&root)
+            .decode_path(super::PathDecoding::Strict)
+            .merge_slashes(true);
+        for path in ["/static/%2541.txt", "/static//%2541.txt"] {
+            let res = decoded
+                .dispatch(Request::get(path).body(Body::empty())?)
+                .await;
+            assert_eq!(
+                body::to_bytes(res.into_body()).await?,
+                "escaped",
+                "{}",
+                path
+            );
+        }
+
+        std::fs::remove_dir_all(&base)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn decode_path() -> anyhow::Result<()> {
+        use super::PathDecoding;
+
+        let echo = BoxHandler::new(|req: Request<Body>| async move {
+            let params = req.extensions().get::<crate::Params>().unwrap();
+            let name = params.get("name").unwrap().to_owned();
+            Ok(Response::new(Body::from(name)))
+        });
+        let app = || Router::new().get("/files/:name", echo.clone());
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+
+        let strict = app().decode_path(PathDecoding::Strict);
+        let res = strict.dispatch(get("/files/caf%C3%A9%2Fx")).await;
+        assert_eq!(body::to_bytes(res.into_body()).await?, "café%2Fx");
+        let res = strict.dispatch(get("/files/a%zzb")).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let res = strict.dispatch(get("/files/a%C3")).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let lenient = app().decode_path(PathDecoding::Lenient);
+        let res = lenient.dispatch(get("/files/a%zzb%20c%")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(body::to_bytes(res.into_body()).await?, "a%zzb c%");
+        let res = lenient.dispatch(get("/files/a%C3")).await;
+        assert_eq!(body::to_bytes(res.into_body()).await?, "a%C3");
+
+        let res = app().dispatch(get("/files/a%20b")).await;
+        assert_eq!(body::to_bytes(res.into_body()).await?, "a%20b");
+
+        Ok(())
+    }
+
+    #[test]
+    fn url_for() {
+        let app = Router::<usize>::new()
+            .get("/users/:id", 1)
+            .named("user")
+            .get("/files/*path", 2)
+            .named("file")
+            .get("/posts/{year:int}/{slug}", 3)
+            .named("post");
+
+        assert_eq!(
+            app.url_for("user", &[("id", "a/b")]).as_deref(),
+            Some("/users/a%2Fb")
+        );
+        assert_eq!(
+            app.url_for("user", &[("id", "John Doe")]).as_deref(),
+            Some("/users/John%20Doe")
+        );
+        assert_eq!(
+            app.url_for("user", &[("id", "café")]).as_deref(),
+            Some("/users/caf%C3%A9")
+        );
+        assert_eq!(
+            app.url_for("file", &[("path", "docs/read me.md")])
+                .as_deref(),
+            Some("/files/docs/read%20me.md")
+        );
+        assert_eq!(
+            app.url_for(
+                "post",
+                &[("slug", "hello-world"), ("year", "2021"), ("x", "y")]
+            )
+            .as_deref(),
+            Some("/posts/2021/hello-world")
+        );
+
+        assert_eq!(app.url_for("post", &[("year", "2021")]), None);
+        assert_eq!(
+            app.url_for("post", &[("year", "soon"), ("slug", "hello-world")]),
+            None
+        );
+        assert_eq!(app.url_for("missing", &[]), None);
+    }
+
+    #[test]
+    fn scoped_names() {
+        let users = Router::new()
+            .name("users")
+            .path("/users")
+            .get("/:id", 1)
+            .named("show")
+            .get("/", 2);
+        let api = Router::new()
+            .name("api")
+            .path("/api")
+            .get("/health", 3)
+            .named("health")
+            .scope(users);
+        let app = Router::<usize>::new()
+            .get("/", 4)
+            .named("home")
+            .scope(api)
+            .scope(Router::new().path("/admin").get("/stats", 5).named("admin"));
+
+        assert_eq!(
+            app.url_for("api.users.show", &[("id", "42")]).as_deref(),
+            Some("/api/users/42")
+        );
+        assert_eq!(
+            app.url_for("api.health", &[]).as_deref(),
+            Some("/api/health")
+        );
+        assert_eq!(app.url_for("home", &[]).as_deref(), Some("/"));
+        assert_eq!(app.url_for("admin", &[]).as_deref(), Some("/admin/stats"));
+        assert_eq!(app.url_for("show", &[("id", "42")]), None);
+        assert_eq!(app.url_for("users.show", &[("id", "42")]), None);
+    }
+
+    #[tokio::test]
+    async fn trace_echo() -> anyhow::Result<()> {
+        let app = Router::new().trace("/*", BoxHandler::new(crate::trace_echo));
+
+        let req = Request::builder()
+            .method(Method::TRACE)
+            .uri("/echo?x=1")
+            .header(header::MAX_FORWARDS, "0")
+            .header("x-probe", "abc")
+            .header(header::COOKIE, "session=secret")
+            .body(Body::empty())?;
+        assert_eq!(req.max_forwards(), Some(0));
+
+        let res = app.dispatch(req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "message/http");
+        assert_eq!(
+            body::to_bytes(res.into_body()).await?,
+            "TRACE /echo?x=1 HTTP/1.1\r\nmax-forwards: 0\r\nx-probe: abc\r\n\r\n"
+        );
+
+        let req = Request::builder()
+            .method(Method::TRACE)
+            .uri("/")
+            .header(header::MAX_FORWARDS, "-1")
+            .body(Body::empty())?;
+        assert_eq!(req.max_forwards(), None);
+        assert_eq!(app.dispatch(req).await.status(), StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fallback() -> anyhow::Result<()> {
+        let text = |s: &'static str| {
+            BoxHandler::new(move |_: Request<Body>| async move { Ok(Response::new(Body::from(s))) })
+        };
+        let legacy = service_fn(|req: Request<Body>| async move {
+            Ok::<_, Infallible>(Response::new(Body::from(format!("legacy {}", req.uri()))))
+        });
+        let get = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+
+        let app = Router::new()
+            .get("/", text("home"))
+            .fallback(text("fallback"))
+            .fallback_service(legacy);
+
+        let res = app.dispatch(get("/")).await;
+        assert_eq!(body::to_bytes(res.into_body()).await?, "home");
+
+        let res = app.dispatch(get("/old/page?x=1")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            body::to_bytes(res.into_body()).await?,
+            "legacy /old/page?x=1"
+        );
+
+        let app = app.fallback(text("fallback"));
+        let res = app.dispatch(get("/old/page")).await;
+        assert_eq!(body::to_bytes(res.into_body()).await?, "fallback");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn produces() -> anyhow::Result<()> {
+        let text = |s: &'static str| {
+            BoxHandler::new(move |_: Request<Body>| async move { Ok(Response::new(Body::from(s))) })
+        };
+        let get = |accept: Option<&str>| {
+            let mut req = Request::get("/report");
+            if let Some(accept) = accept {
+                req = req.header(header::ACCEPT, accept);
+            }
+            req.body(Body::empty()).unwrap()
+        };
+
+        let app = Router::new()
+            .get("/report", text("json"))
+            .produces(mime::APPLICATION_JSON)
+            .get("/report", text("xml"))
+            .produces("application/xml".parse()?);
+
+        for (accept, expected) in [
+            (None, "json"),
+            (Some("application/json"), "json"),
+            (Some("application/xml"), "xml"),
+            (Some("application/json;q=0.5, application/*"), "xml"),
+        ] {
+            let res = app.dispatch(get(accept)).await;
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(body::to_bytes(res.into_body()).await?, expected);
+        }
+
+        let res = app.dispatch(get(Some("text/html"))).await;
+        assert_eq!(res.status(), StatusCode::NOT_ACCEPTABLE);
+
+        let app = app.get("/report", text("any"));
+        let res = app.dispatch(get(Some("text/html"))).await;
+        assert_eq!(body::to_bytes(res.into_body()).await?, "any");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn server_header() {
+        let ok = BoxHandler::new(|_: Request<Body>| async {
+            let mut res = Response::new(Body::empty());
+            res.headers_mut()
+                .insert(header::SERVER, header::HeaderValue::from_static("handler"));
+            Ok(res)
+        });
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+
+        let app = Router::new()
+            .get("/", ok.clone())
+            .server_header(Some(header::HeaderValue::from_static("hyperstone")));
+        for path in ["/", "/missing"] {
+            let res = app.dispatch(get(path)).await;
+            assert_eq!(res.headers()[header::SERVER], "hyperstone");
+        }
+
+        let app = Router::new().get("/", ok).server_header(None);
+        for path in ["/", "/missing"] {
+            let res = app.dispatch(get(path)).await;
+            assert!(res.headers().get(header::SERVER).is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn wrap() {
+        let ok = || BoxHandler::new(|_: Request<Body>| async { Ok(Response::new(Body::empty())) });
+
+        let app = Router::new()
+            .get("/", ok())
+            .post("/users", ok())
+            .wrap(|mut res| {
+                res.headers_mut().insert(
+                    header::SERVER,
+                    header::HeaderValue::from_static("hyperstone"),
+                );
+                res
+            })
+            .wrap(|mut res| {
+                let server = res.headers_mut().remove(header::SERVER).unwrap();
+                res.headers_mut().insert("x-server", server);
+                res
+            });
+
+        for (method, uri, status) in [
+            (Method::GET, "/", StatusCode::OK),
+            (Method::POST, "/users", StatusCode::OK),
+            (Method::GET, "/missing", StatusCode::NOT_FOUND),
+        ] {
+            let req = Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+            let res = app.dispatch(req).await;
+            assert_eq!(res.status(), status);
+            assert_eq!(res.headers()["x-server"], "hyperstone");
+            assert!(res.headers().get(header::SERVER).is_none());
+        }
+
+        let stamp = |value: &'static str| {
+            move |mut res: Response<Body>| {
+                res.headers_mut()
+                    .append("x-scope", header::HeaderValue::from_static(value));
+                res
+            }
+        };
+        let app = Router::new()
+            .get("/", ok())
+            .scope(
+                Router::new()
+                    .path("/api")
+                    .get("/users", ok())
+                    .wrap(stamp("api")),
+            )
+            .wrap(stamp("app"));
+
+        let res = app
+            .dispatch(Request::get("/api/users").body(Body::empty()).unwrap())
+            .await;
+        let scopes = res.headers().get_all("x-scope").iter().collect::<Vec<_>>();
+        assert_eq!(scopes, ["api", "app"]);
+        let res = app
+            .dispatch(Request::get("/").body(Body::empty()).unwrap())
+            .await;
+        let scopes = res.headers().get_all("x-scope").iter().collect::<Vec<_>>();
+        assert_eq!(scopes, ["app"]);
+    }
+
+    #[tokio::test]
+    async fn expects() {
+        let app = Router::new()
+            .post(
+                "/users",
+                BoxHandler::new(|_: Request<Body>| async { Ok(Response::new(Body::empty())) }),
+            )
+            .expects(mime::APPLICATION_JSON);
+
+        let req = |ct: &'static str| {
+            Request::builder()
+                .method(Method::POST)
+                .uri("/users")
+                .header(header::CONTENT_TYPE, ct)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let res = app.dispatch(req("text/plain")).await;
+        assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let res = app.dispatch(req("application/json; charset=utf-8")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn mount() -> anyhow::Result<()> {
+        let echo = service_fn(|req: Request<Body>| async move {
+            Ok::<_, Infallible>(Response::new(Body::from(req.uri().to_string())))
+        });
+
+        let app = Router::new()
+            .get(
+                "/static/special",
+                BoxHandler::new(|_: Request<Body>| async { Ok(Response::new("route".into())) }),
+            )
+            .mount("/static", echo);
+
+        let call = |uri: &'static str| {
+            app.dispatch(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        };
+
+        let res = call("/static/css/app.css?v=1").await;
+        assert_eq!(body::to_bytes(res.into_body()).await?, "/css/app.css?v=1");
+
+        let res = call("/static").await;
+        assert_eq!(body::to_bytes(res.into_body()).await?, "/");
+
+        let res = call("/static/special").await;
+        assert_eq!(body::to_bytes(res.into_body()).await?, "route");
+
+        let app = Router::new()
+            .mount("/static", echo)
+            .decode_path(super::PathDecoding::Strict);
+        let res = app
+            .dispatch(Request::get("/static/caf%C3%A9/a%20b?v=1").body(Body::empty())?)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            body::to_bytes(res.into_body()).await?,
+            "/caf%C3%A9/a%20b?v=1"
+        );
+
+        Ok(())
     }
 }
@@ -0,0 +1,282 @@
+use crate::Method;
+use std::{fmt, time::Duration};
+
+/// A `Content-Security-Policy` built from directives
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContentSecurityPolicy(Vec<(String, Vec<String>)>);
+
+impl ContentSecurityPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a directive, e.g. `directive("script-src", &["'self'", "cdn.example.com"])`,
+    /// replacing an earlier one of the same name.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the name is not a directive name, or a source contains whitespace, `;`
+    /// or `,`, which would change the meaning of the policy.
+    pub fn directive(mut self, name: &str, sources: &[&str]) -> Self {
+        assert!(
+            !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-'),
+            "invalid directive name `{}`",
+            name
+        );
+        for source in sources {
+            assert!(
+                !source.is_empty()
+                    && source
+                        .bytes()
+                        .all(|b| b.is_ascii_graphic() && b != b';' && b != b','),
+                "invalid source `{}` of directive `{}`",
+                source,
+                name
+            );
+        }
+
+        let name = name.to_ascii_lowercase();
+        let sources = sources.iter().map(|s| (*s).to_owned()).collect();
+        match self.0.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, s)) => *s = sources,
+            None => self.0.push((name, sources)),
+        }
+        self
+    }
+}
+
+impl fmt::Display for ContentSecurityPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (name, sources)) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            f.write_str(name)?;
+            for source in sources {
+                write!(f, " {}", source)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A `WWW-Authenticate` challenge, e.g. `Bearer realm="api", error="invalid_token"`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Challenge {
+    scheme: String,
+    params: Vec<(String, String)>,
+}
+
+impl Challenge {
+    /// A challenge of an auth scheme, without parameters.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the scheme is not a token.
+    pub fn new(scheme: &str) -> Self {
+        assert!(is_token(scheme), "invalid auth scheme `{}`", scheme);
+        Self {
+            scheme: scheme.to_owned(),
+            params: Vec::new(),
+        }
+    }
+
+    /// `Basic realm="realm", charset="UTF-8"`
+    pub fn basic(realm: &str) -> Self {
+        Self::new("Basic")
+            .param("realm", realm)
+            .param("charset", "UTF-8")
+    }
+
+    /// `Bearer realm="realm"`, see RFC 6750
+    pub fn bearer(realm: &str) -> Self {
+        Self::new("Bearer").param("realm", realm)
+    }
+
+    /// Adds a parameter, quoting and escaping the value.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the name is not a token, or the value contains control characters.
+    pub fn param(mut self, name: &str, value: &str) -> Self {
+        assert!(is_token(name), "invalid auth parameter `{}`", name);
+        assert!(
+            !value.chars().any(|c| c.is_control()),
+            "invalid value of auth parameter `{}`",
+            name
+        );
+        self.params.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// The OAuth `error` code, e.g. `invalid_token` or `insufficient_scope`
+    pub fn error(self, code: &str) -> Self {
+        self.param("error", code)
+    }
+
+    /// The human readable `error_description` of the OAuth error
+    pub fn error_description(self, description: &str) -> Self {
+        self.param("error_description", description)
+    }
+}
+
+impl fmt::Display for Challenge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.scheme)?;
+        for (i, (name, value)) in self.params.iter().enumerate() {
+            f.write_str(if i == 0 { " " } else { ", " })?;
+            write!(f, "{}=\"", name)?;
+            for c in value.chars() {
+                if c == '"' || c == '\\' {
+                    f.write_str("\\")?;
+                }
+                write!(f, "{}", c)?;
+            }
+            f.write_str("\"")?;
+        }
+        Ok(())
+    }
+}
+
+/// A CORS policy for one allowed origin, or any origin with `*`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cors {
+    origin: String,
+    methods: Vec<Method>,
+    headers: Vec<String>,
+    max_age: Option<Duration>,
+    credentials: bool,
+}
+
+impl Cors {
+    /// A policy allowing `origin`, e.g. `https://example.com`, or any origin with `*`.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the origin contains whitespace, `,` or control characters.
+    pub fn new(origin: &str) -> Self {
+        assert!(
+            !origin.is_empty() && origin.bytes().all(|b| b.is_ascii_graphic() && b != b','),
+            "invalid origin `{}`",
+            origin
+        );
+        Self {
+            origin: origin.to_owned(),
+            methods: Vec::new(),
+            headers: Vec::new(),
+            max_age: None,
+            credentials: false,
+        }
+    }
+
+    /// The methods preflights allow, `Access-Control-Allow-Methods`
+    pub fn allow_methods(mut self, methods: &[Method]) -> Self {
+        self.methods = methods.to_vec();
+        self
+    }
+
+    /// The request headers preflights allow, `Access-Control-Allow-Headers`
+    ///
+    /// # Panics
+    ///
+    /// Panics when a name is not a token.
+    pub fn allow_headers(mut self, headers: &[&str]) -> Self {
+        for name in headers {
+            assert!(is_token(name), "invalid header name `{}`", name);
+        }
+        self.headers = headers.iter().map(|h| h.to_ascii_lowercase()).collect();
+        self
+    }
+
+    /// How long preflight results may be cached, `Access-Control-Max-Age`
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age.replace(max_age);
+        self
+    }
+
+    /// Lets requests carry credentials, `Access-Control-Allow-Credentials: true`.
+    ///
+    /// As browsers refuse credentialed responses allowing `*`, a policy allowing any origin
+    /// then echoes the request's `Origin` instead.
+    pub fn allow_credentials(mut self, b: bool) -> Self {
+        self.credentials = b;
+        self
+    }
+
+    /// The `Access-Control-Allow-Origin` value for a request from `origin`, `None` when the
+    /// origin is not allowed
+    pub(crate) fn allow_origin<'a>(&'a self, origin: Option<&'a str>) -> Option<&'a str> {
+        match (self.origin.as_str(), origin) {
+            ("*", _) if !self.credentials => Some("*"),
+            ("*", origin) => origin.filter(|o| *o != "null"),
+            (allowed, Some(origin)) if allowed.eq_ignore_ascii_case(origin) => Some(origin),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn credentials(&self) -> bool {
+        self.credentials
+    }
+
+    pub(crate) fn methods(&self) -> &[Method] {
+        &self.methods
+    }
+
+    pub(crate) fn headers(&self) -> &[String] {
+        &self.headers
+    }
+
+    pub(crate) fn max_age_secs(&self) -> Option<u64> {
+        self.max_age.map(|d| d.as_secs())
+    }
+}
+
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+/// The values of `X-Frame-Options`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameOptions {
+    Deny,
+    SameOrigin,
+}
+
+impl FrameOptions {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Deny => "DENY",
+            Self::SameOrigin => "SAMEORIGIN",
+        }
+    }
+}
+
+/// The values of `Referrer-Policy`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReferrerPolicy {
+    NoReferrer,
+    NoReferrerWhenDowngrade,
+    Origin,
+    OriginWhenCrossOrigin,
+    SameOrigin,
+    StrictOrigin,
+    StrictOriginWhenCrossOrigin,
+    UnsafeUrl,
+}
+
+impl ReferrerPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NoReferrer => "no-referrer",
+            Self::NoReferrerWhenDowngrade => "no-referrer-when-downgrade",
+            Self::Origin => "origin",
+            Self::OriginWhenCrossOrigin => "origin-when-cross-origin",
+            Self::SameOrigin => "same-origin",
+            Self::StrictOrigin => "strict-origin",
+            Self::StrictOriginWhenCrossOrigin => "strict-origin-when-cross-origin",
+            Self::UnsafeUrl => "unsafe-url",
+        }
+    }
+}
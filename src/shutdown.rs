@@ -0,0 +1,182 @@
+use crate::{header, header::HeaderValue, Body, BoxHandler, Request, Response, Router, StatusCode};
+use futures_util::{
+    future::{BoxFuture, FutureExt, Shared},
+    task::AtomicWaker,
+};
+use std::{
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+use tower_service::Service;
+
+type Signal = Shared<Pin<Box<dyn Future<Output = ()> + Send>>>;
+
+struct Inner {
+    router: Router<BoxHandler>,
+    signal: Signal,
+    in_flight: AtomicUsize,
+    idle: AtomicWaker,
+}
+
+impl Inner {
+    fn shutting_down(&self) -> bool {
+        self.signal.clone().now_or_never().is_some()
+    }
+}
+
+/// A tower service dispatching to a [`Router`], which drains on a shutdown signal, see
+/// [`Router::into_service_with_shutdown`]
+#[derive(Clone)]
+pub struct RouterService(Arc<Inner>);
+
+impl RouterService {
+    pub(crate) fn new<F>(router: Router<BoxHandler>, signal: F) -> Self
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let signal: Pin<Box<dyn Future<Output = ()> + Send>> = Box::pin(signal);
+        Self(Arc::new(Inner {
+            router,
+            signal: signal.shared(),
+            in_flight: AtomicUsize::new(0),
+            idle: AtomicWaker::new(),
+        }))
+    }
+
+    /// The number of requests being handled
+    pub fn in_flight(&self) -> usize {
+        self.0.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once the signal has fired and the requests in flight have finished, e.g. for
+    /// hyper's `with_graceful_shutdown`. Only the last future polled is woken.
+    pub fn drained(&self) -> Drained {
+        Drained {
+            signal: self.0.signal.clone(),
+            inner: self.0.clone(),
+        }
+    }
+}
+
+impl Service<Request<Body>> for RouterService {
+    type Response = Response<Body>;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        // counts the request before checking the signal, the reverse of `Drained`, so a
+        // `Drained` which saw no request in flight after the signal fired makes this refuse
+        let inner = self.0.clone();
+        inner.in_flight.fetch_add(1, Ordering::SeqCst);
+        let guard = InFlight(inner.clone());
+
+        if inner.shutting_down() {
+            drop(guard);
+            let mut res = Response::new(Body::empty());
+            *res.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+            res.headers_mut()
+                .insert(header::CONNECTION, HeaderValue::from_static("close"));
+            return Box::pin(async { Ok(res) });
+        }
+
+        Box::pin(async move {
+            let _guard = guard;
+            Ok(inner.router.dispatch(req).await)
+        })
+    }
+}
+
+/// Counts a request as in flight until dropped
+struct InFlight(Arc<Inner>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if self.0.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.wake();
+        }
+    }
+}
+
+/// The future of [`RouterService::drained`]
+pub struct Drained {
+    signal: Signal,
+    inner: Arc<Inner>,
+}
+
+impl Future for Drained {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.signal.poll_unpin(cx).is_pending() {
+            return Poll::Pending;
+        }
+
+        let inner = &self.inner;
+        inner.idle.register(cx.waker());
+        if inner.in_flight.load(Ordering::SeqCst) == 0 {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Body, BoxHandler, Request, Response, Router, StatusCode};
+    use futures_util::FutureExt;
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::oneshot;
+    use tower_service::Service;
+
+    #[tokio::test]
+    async fn drains_in_flight_requests() -> anyhow::Result<()> {
+        let (release, released) = oneshot::channel::<()>();
+        let released = Arc::new(Mutex::new(Some(released)));
+        let (stop, signal) = oneshot::channel::<()>();
+
+        let app = Router::new().get(
+            "/slow",
+            BoxHandler::new(move |_: Request<Body>| {
+                let released = released.lock().unwrap().take();
+                async move {
+                    if let Some(released) = released {
+                        released.await?;
+                    }
+                    Ok(Response::new(Body::from("done")))
+                }
+            }),
+        );
+        let mut service = app.into_service_with_shutdown(signal.map(|_| ()));
+
+        let in_flight = tokio::spawn(service.call(Request::get("/slow").body(Body::empty())?));
+        assert_eq!(service.in_flight(), 1);
+
+        stop.send(()).unwrap();
+        let res = service
+            .call(Request::get("/slow").body(Body::empty())?)
+            .await?;
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(service.in_flight(), 1);
+        assert!(service.drained().now_or_never().is_none());
+
+        release.send(()).unwrap();
+        let res = in_flight.await??;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(crate::body::to_bytes(res.into_body()).await?, "done");
+        service.drained().await;
+        assert_eq!(service.in_flight(), 0);
+
+        Ok(())
+    }
+}
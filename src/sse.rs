@@ -0,0 +1,153 @@
+//! Server-sent events (`text/event-stream`)
+
+use bytes::{BufMut, Bytes, BytesMut};
+use std::time::Duration;
+
+/// An event of a [`ResponseExt::event_stream`](crate::ResponseExt::event_stream)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Event {
+    comment: Option<String>,
+    event: Option<String>,
+    id: Option<String>,
+    retry: Option<Duration>,
+    data: Option<String>,
+}
+
+impl Event {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A comment, ignored by clients, e.g. an empty one as a keep-alive ping
+    pub fn comment(comment: impl Into<String>) -> Self {
+        Self {
+            comment: Some(comment.into()),
+            ..Self::default()
+        }
+    }
+
+    /// The data, sent as one `data:` line per line
+    pub fn data(mut self, data: impl Into<String>) -> Self {
+        self.data.replace(data.into());
+        self
+    }
+
+    /// The event type, `message` when not set.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the name contains a line break.
+    pub fn event(mut self, event: impl Into<String>) -> Self {
+        let event = event.into();
+        assert!(
+            !event.contains(['\r', '\n']),
+            "invalid event type `{}`",
+            event
+        );
+        self.event.replace(event);
+        self
+    }
+
+    /// The id the client sends back as `Last-Event-ID` when reconnecting.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the id contains a line break or NUL.
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        let id = id.into();
+        assert!(
+            !id.contains(['\r', '\n', '\0']),
+            "invalid event id `{}`",
+            id
+        );
+        self.id.replace(id);
+        self
+    }
+
+    /// The reconnection delay of the client, sent in whole milliseconds
+    pub fn retry(mut self, retry: Duration) -> Self {
+        self.retry.replace(retry);
+        self
+    }
+
+    /// Encodes the event in the wire format, terminated by a blank line
+    pub fn encode(&self) -> Bytes {
+        fn field(buf: &mut BytesMut, name: &str, value: &str) {
+            buf.put_slice(name.as_bytes());
+            if !value.is_empty() {
+                buf.put_slice(b": ");
+                buf.put_slice(value.as_bytes());
+            }
+            buf.put_u8(b'\n');
+        }
+
+        let mut buf = BytesMut::new();
+        if let Some(comment) = &self.comment {
+            lines(comment).for_each(|line| {
+                buf.put_u8(b':');
+                buf.put_slice(line.as_bytes());
+                buf.put_u8(b'\n');
+            });
+        }
+        if let Some(event) = &self.event {
+            field(&mut buf, "event", event);
+        }
+        if let Some(id) = &self.id {
+            field(&mut buf, "id", id);
+        }
+        if let Some(retry) = self.retry {
+            field(&mut buf, "retry", &retry.as_millis().to_string());
+        }
+        if let Some(data) = &self.data {
+            lines(data).for_each(|line| field(&mut buf, "data", line));
+        }
+        buf.put_u8(b'\n');
+        buf.freeze()
+    }
+}
+
+/// Splits on `\r\n`, `\r` and `\n`, the line breaks of the event stream format
+fn lines(s: &str) -> impl Iterator<Item = &str> {
+    let mut rest = Some(s);
+    std::iter::from_fn(move || {
+        let s = rest?;
+        match s.find(['\r', '\n']) {
+            Some(i) => {
+                let skip = if s[i..].starts_with("\r\n") { 2 } else { 1 };
+                rest = Some(&s[i + skip..]);
+                Some(&s[..i])
+            }
+            None => rest.take(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Event;
+    use std::time::Duration;
+
+    #[test]
+    fn encode() {
+        let event = Event::new()
+            .event("update")
+            .id("42")
+            .retry(Duration::from_secs(3))
+            .data("line 1\nline 2\r\n\rline 4");
+        assert_eq!(
+            event.encode(),
+            "event: update\nid: 42\nretry: 3000\n\
+             data: line 1\ndata: line 2\ndata\ndata: line 4\n\n"
+        );
+
+        assert_eq!(Event::comment("").encode(), ":\n\n");
+        assert_eq!(Event::comment(" ping").encode(), ": ping\n\n");
+        assert_eq!(Event::new().data("").encode(), "data\n\n");
+    }
+
+    #[test]
+    #[should_panic]
+    fn event_injection() {
+        Event::new().event("update\ndata: forged");
+    }
+}
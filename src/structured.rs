@@ -0,0 +1,545 @@
+//! Structured field values for HTTP (RFC 8941)
+
+/// A bare item of a structured field
+#[derive(Clone, Debug, PartialEq)]
+pub enum BareItem {
+    Integer(i64),
+    Decimal(f64),
+    String(String),
+    Token(String),
+    ByteSequence(Vec<u8>),
+    Boolean(bool),
+}
+
+/// The parameters of an item or inner list, in order
+pub type Parameters = Vec<(String, BareItem)>;
+
+/// An item with its parameters
+#[derive(Clone, Debug, PartialEq)]
+pub struct Item {
+    pub bare: BareItem,
+    pub params: Parameters,
+}
+
+/// A member of a list or dictionary
+#[derive(Clone, Debug, PartialEq)]
+pub enum Member {
+    Item(Item),
+    InnerList(Vec<Item>, Parameters),
+}
+
+/// A parsed structured field value
+#[derive(Clone, Debug, PartialEq)]
+pub enum StructuredValue {
+    Item(Item),
+    List(Vec<Member>),
+    Dictionary(Vec<(String, Member)>),
+}
+
+impl BareItem {
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Self::Integer(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Decimals, and integers widened to decimals
+    pub fn as_decimal(&self) -> Option<f64> {
+        match self {
+            Self::Decimal(n) => Some(*n),
+            Self::Integer(n) => Some(*n as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_token(&self) -> Option<&str> {
+        match self {
+            Self::Token(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::ByteSequence(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl Item {
+    pub fn param(&self, key: &str) -> Option<&BareItem> {
+        param(&self.params, key)
+    }
+}
+
+impl Member {
+    pub fn as_item(&self) -> Option<&Item> {
+        match self {
+            Self::Item(item) => Some(item),
+            Self::InnerList(..) => None,
+        }
+    }
+
+    pub fn as_inner_list(&self) -> Option<&[Item]> {
+        match self {
+            Self::Item(_) => None,
+            Self::InnerList(items, _) => Some(items),
+        }
+    }
+
+    pub fn param(&self, key: &str) -> Option<&BareItem> {
+        match self {
+            Self::Item(item) => item.param(key),
+            Self::InnerList(_, params) => param(params, key),
+        }
+    }
+}
+
+impl StructuredValue {
+    /// Parses a field whose type is not known up front.
+    ///
+    /// A single item parses as [`Item`](Self::Item). Otherwise the value is a
+    /// [`Dictionary`](Self::Dictionary) when it has at least one `key=value` member, else a
+    /// [`List`](Self::List). Prefer [`parse_item`], [`parse_list`] or [`parse_dictionary`]
+    /// when the field's type is known.
+    pub fn parse(s: &str) -> Option<Self> {
+        if let Some(item) = parse_item(s) {
+            return Some(Self::Item(item));
+        }
+
+        match Parser::new(s).dictionary() {
+            Some((dictionary, true)) => Some(Self::Dictionary(dictionary)),
+            _ => parse_list(s).map(Self::List),
+        }
+    }
+
+    pub fn as_item(&self) -> Option<&Item> {
+        match self {
+            Self::Item(item) => Some(item),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Member]> {
+        match self {
+            Self::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    pub fn as_dictionary(&self) -> Option<&[(String, Member)]> {
+        match self {
+            Self::Dictionary(dictionary) => Some(dictionary),
+            _ => None,
+        }
+    }
+
+    /// Looks up a dictionary member
+    pub fn get(&self, key: &str) -> Option<&Member> {
+        self.as_dictionary()?
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, m)| m)
+    }
+}
+
+pub fn parse_item(s: &str) -> Option<Item> {
+    let mut parser = Parser::new(s);
+    let item = parser.item()?;
+    parser.end().then_some(item)
+}
+
+pub fn parse_list(s: &str) -> Option<Vec<Member>> {
+    Parser::new(s).list()
+}
+
+pub fn parse_dictionary(s: &str) -> Option<Vec<(String, Member)>> {
+    Parser::new(s)
+        .dictionary()
+        .map(|(dictionary, _)| dictionary)
+}
+
+fn param<'a>(params: &'a [(String, BareItem)], key: &str) -> Option<&'a BareItem> {
+    params.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(s: &'a str) -> Self {
+        let mut parser = Self {
+            input: s.trim_end_matches(' ').as_bytes(),
+            pos: 0,
+        };
+        parser.skip(b" ");
+        parser
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, b: u8) -> bool {
+        let found = self.peek() == Some(b);
+        self.pos += usize::from(found);
+        found
+    }
+
+    fn skip(&mut self, bytes: &[u8]) {
+        while self.peek().is_some_and(|b| bytes.contains(&b)) {
+            self.pos += 1;
+        }
+    }
+
+    fn end(&self) -> bool {
+        self.pos == self.input.len()
+    }
+
+    /// Parses the separator after a list or dictionary member, `false` at the end
+    fn separator(&mut self) -> Option<bool> {
+        self.skip(b" \t");
+        if self.end() {
+            return Some(false);
+        }
+        if !self.eat(b',') {
+            return None;
+        }
+        self.skip(b" \t");
+        (!self.end()).then_some(true)
+    }
+
+    fn list(&mut self) -> Option<Vec<Member>> {
+        let mut members = Vec::new();
+        if self.end() {
+            return Some(members);
+        }
+        loop {
+            members.push(self.member()?);
+            if !self.separator()? {
+                return Some(members);
+            }
+        }
+    }
+
+    /// Also tells whether any member has an explicit value
+    fn dictionary(&mut self) -> Option<(Vec<(String, Member)>, bool)> {
+        let mut members: Vec<(String, Member)> = Vec::new();
+        let mut valued = false;
+        if self.end() {
+            return Some((members, valued));
+        }
+        loop {
+            let key = self.key()?;
+            let member = if self.eat(b'=') {
+                valued = true;
+                self.member()?
+            } else {
+                Member::Item(Item {
+                    bare: BareItem::Boolean(true),
+                    params: self.parameters()?,
+                })
+            };
+
+            match members.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, m)) => *m = member,
+                None => members.push((key, member)),
+            }
+
+            if !self.separator()? {
+                return Some((members, valued));
+            }
+        }
+    }
+
+    fn member(&mut self) -> Option<Member> {
+        if !self.eat(b'(') {
+            return self.item().map(Member::Item);
+        }
+
+        let mut items = Vec::new();
+        loop {
+            self.skip(b" ");
+            if self.eat(b')') {
+                return Some(Member::InnerList(items, self.parameters()?));
+            }
+            items.push(self.item()?);
+            if !matches!(self.peek(), Some(b' ') | Some(b')')) {
+                return None;
+            }
+        }
+    }
+
+    fn item(&mut self) -> Option<Item> {
+        Some(Item {
+            bare: self.bare_item()?,
+            params: self.parameters()?,
+        })
+    }
+
+    fn parameters(&mut self) -> Option<Parameters> {
+        let mut params: Parameters = Vec::new();
+        while self.eat(b';') {
+            self.skip(b" ");
+            let key = self.key()?;
+            let value = if self.eat(b'=') {
+                self.bare_item()?
+            } else {
+                BareItem::Boolean(true)
+            };
+
+            match params.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, v)) => *v = value,
+                None => params.push((key, value)),
+            }
+        }
+        Some(params)
+    }
+
+    fn key(&mut self) -> Option<String> {
+        let start = self.pos;
+        if !self.peek()?.is_ascii_lowercase() && self.peek()? != b'*' {
+            return None;
+        }
+        while self
+            .peek()
+            .is_some_and(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b"_-.*".contains(&b))
+        {
+            self.pos += 1;
+        }
+        self.str_from(start)
+    }
+
+    fn bare_item(&mut self) -> Option<BareItem> {
+        match self.peek()? {
+            b'-' | b'0'..=b'9' => self.number(),
+            b'"' => self.string(),
+            b':' => self.byte_sequence(),
+            b'?' => {
+                self.pos += 1;
+                let b = match self.peek()? {
+                    b'0' => false,
+                    b'1' => true,
+                    _ => return None,
+                };
+                self.pos += 1;
+                Some(BareItem::Boolean(b))
+            }
+            b if b.is_ascii_alphabetic() || b == b'*' => self.token(),
+            _ => None,
+        }
+    }
+
+    fn number(&mut self) -> Option<BareItem> {
+        let start = self.pos;
+        self.eat(b'-');
+        let digits = self.pos;
+        while self.peek().is_some_and(|b| b.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let integral = self.pos - digits;
+        if integral == 0 {
+            return None;
+        }
+
+        if !self.eat(b'.') {
+            if integral > 15 {
+                return None;
+            }
+            return self.str_from(start)?.parse().ok().map(BareItem::Integer);
+        }
+
+        let fraction = self.pos;
+        while self.peek().is_some_and(|b| b.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if integral > 12 || !(1..=3).contains(&(self.pos - fraction)) {
+            return None;
+        }
+        self.str_from(start)?.parse().ok().map(BareItem::Decimal)
+    }
+
+    fn string(&mut self) -> Option<BareItem> {
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            match self.peek()? {
+                b'"' => {
+                    self.pos += 1;
+                    return Some(BareItem::String(s));
+                }
+                b'\\' => {
+                    self.pos += 1;
+                    match self.peek()? {
+                        b @ b'"' | b @ b'\\' => s.push(b as char),
+                        _ => return None,
+                    }
+                }
+                b @ 0x20..=0x7e => s.push(b as char),
+                _ => return None,
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn token(&mut self) -> Option<BareItem> {
+        let start = self.pos;
+        self.pos += 1;
+        while self
+            .peek()
+            .is_some_and(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~:/".contains(&b))
+        {
+            self.pos += 1;
+        }
+        self.str_from(start).map(BareItem::Token)
+    }
+
+    fn byte_sequence(&mut self) -> Option<BareItem> {
+        self.pos += 1;
+        let start = self.pos;
+        while self.peek()? != b':' {
+            self.pos += 1;
+        }
+        let encoded = &self.input[start..self.pos];
+        self.pos += 1;
+        decode_base64(encoded).map(BareItem::ByteSequence)
+    }
+
+    fn str_from(&self, start: usize) -> Option<String> {
+        std::str::from_utf8(&self.input[start..self.pos])
+            .ok()
+            .map(ToOwned::to_owned)
+    }
+}
+
+/// Decodes standard base64, padding optional
+pub(crate) fn decode_base64(input: &[u8]) -> Option<Vec<u8>> {
+    let input = match input.iter().position(|b| *b == b'=') {
+        Some(i) if input[i..].iter().all(|b| *b == b'=') && input.len().is_multiple_of(4) => {
+            &input[..i]
+        }
+        Some(_) => return None,
+        None => input,
+    };
+
+    let mut bytes = Vec::with_capacity(input.len() * 3 / 4);
+    let (mut acc, mut bits) = (0u32, 0);
+    for b in input {
+        let v = match b {
+            b'A'..=b'Z' => b - b'A',
+            b'a'..=b'z' => b - b'a' + 26,
+            b'0'..=b'9' => b - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        acc = (acc << 6) | u32::from(v);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((acc >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dictionary() {
+        let value =
+            StructuredValue::parse("a=1, b=?0, c, d=(\"x\" y);q=0.5, e=:aGVsbG8=:;p, a=2").unwrap();
+
+        assert_eq!(
+            value.get("a").and_then(Member::as_item).map(|i| &i.bare),
+            Some(&BareItem::Integer(2))
+        );
+        assert_eq!(
+            value.get("b").and_then(Member::as_item).map(|i| &i.bare),
+            Some(&BareItem::Boolean(false))
+        );
+        assert_eq!(
+            value.get("c").and_then(Member::as_item).map(|i| &i.bare),
+            Some(&BareItem::Boolean(true))
+        );
+
+        let d = value.get("d").unwrap();
+        let items = d.as_inner_list().unwrap();
+        assert_eq!(items[0].bare.as_str(), Some("x"));
+        assert_eq!(items[1].bare.as_token(), Some("y"));
+        assert_eq!(d.param("q").and_then(BareItem::as_decimal), Some(0.5));
+
+        let e = value.get("e").and_then(Member::as_item).unwrap();
+        assert_eq!(e.bare.as_bytes(), Some(&b"hello"[..]));
+        assert_eq!(e.param("p"), Some(&BareItem::Boolean(true)));
+
+        let keys = value
+            .as_dictionary()
+            .unwrap()
+            .iter()
+            .map(|(k, _)| k.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(keys, ["a", "b", "c", "d", "e"]);
+    }
+
+    #[test]
+    fn list() {
+        let value = StructuredValue::parse("sugar, tea;q=1, \"rum\", -12.5, ()").unwrap();
+        let list = value.as_list().unwrap();
+
+        assert_eq!(list.len(), 5);
+        assert_eq!(list[0].as_item().unwrap().bare.as_token(), Some("sugar"));
+        assert_eq!(list[1].param("q").and_then(BareItem::as_integer), Some(1));
+        assert_eq!(list[2].as_item().unwrap().bare.as_str(), Some("rum"));
+        assert_eq!(list[3].as_item().unwrap().bare.as_decimal(), Some(-12.5));
+        assert_eq!(list[4].as_inner_list(), Some(&[][..]));
+    }
+
+    #[test]
+    fn item() {
+        let item = parse_item("text/html;level=1").unwrap();
+        assert_eq!(item.bare.as_token(), Some("text/html"));
+        assert_eq!(item.param("level").and_then(BareItem::as_integer), Some(1));
+
+        assert_eq!(
+            StructuredValue::parse("?1").and_then(|v| v.as_item().cloned()),
+            Some(Item {
+                bare: BareItem::Boolean(true),
+                params: Vec::new()
+            })
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(parse_list("a,"), None);
+        assert_eq!(parse_list("a b"), None);
+        assert_eq!(parse_list("1234567890123456"), None);
+        assert_eq!(parse_list("1.2345"), None);
+        assert_eq!(parse_list("\"unterminated"), None);
+        assert_eq!(parse_list("\"bad \\n escape\""), None);
+        assert_eq!(parse_dictionary("A=1"), None);
+        assert_eq!(parse_item("?2"), None);
+        assert_eq!(parse_item(":not base64!:"), None);
+    }
+}
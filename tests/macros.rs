@@ -0,0 +1,58 @@
+use hyperstone::{
+    anyhow::Result, body, path, route, Body, BoxHandler, MatchedPath, Params, Request, Response,
+    Router, StatusCode,
+};
+
+#[route(GET, "/users/:id", name = "user")]
+async fn user(req: Request<Body>) -> Result<Response<Body>> {
+    let id = req.extensions().get::<Params>().and_then(|p| p.get("id"));
+    let name = req.extensions().get::<MatchedPath>().and_then(|m| m.name());
+    let body = format!("{} {}", name.unwrap_or_default(), id.unwrap_or_default());
+    Ok(Response::new(Body::from(body)))
+}
+
+#[route(ANY, "/ping")]
+async fn ping(_: Request<Body>) -> Result<Response<Body>> {
+    Ok(Response::new(Body::from("pong")))
+}
+
+#[tokio::test]
+async fn from_registered() -> Result<()> {
+    let app = Router::from_registered();
+
+    let res = app
+        .dispatch(Request::get("/users/42").body(Body::empty())?)
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(body::to_bytes(res.into_body()).await?, "user 42");
+
+    let res = app
+        .dispatch(Request::delete("/ping").body(Body::empty())?)
+        .await;
+    assert_eq!(body::to_bytes(res.into_body()).await?, "pong");
+
+    let res = app
+        .dispatch(Request::post("/users/42").body(Body::empty())?)
+        .await;
+    assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn path() -> Result<()> {
+    let handler = BoxHandler::new(|_: Request<Body>| async { Ok(Response::new(Body::from("ok"))) });
+    let app = Router::new().get(path!("/posts/{id:int}/comments/:comment"), handler);
+
+    let res = app
+        .dispatch(Request::get("/posts/7/comments/x").body(Body::empty())?)
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    Ok(())
+}
+
+#[test]
+fn path_ui() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,5 @@
+use hyperstone::path;
+
+fn main() {
+    let _ = path!("/files/{name:float}/*rest");
+}